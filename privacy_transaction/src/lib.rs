@@ -0,0 +1,5 @@
+#[path = "CryptoBulletin.rs"]
+mod crypto_bulletin;
+mod ring_signature;
+
+pub use crate::crypto_bulletin::{CryptoBulletin, Message};
@@ -1,6 +0,0 @@
-pub mod CryptoBulletin;
-pub mod ring_signature;
-
-fn main() {
-    println!("Hello, world!");
-}
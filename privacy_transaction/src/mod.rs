@@ -1,3 +0,0 @@
-pub mod CryptoBulletin;
-
-pub mod ring_signature;
\ No newline at end of file
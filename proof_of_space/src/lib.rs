@@ -0,0 +1,7 @@
+pub mod circuit;
+pub mod mimc;
+mod proof_of_space;
+
+pub use crate::proof_of_space::prover::{prepare_space, mark_space, response_1, construct_circuit, response_2};
+pub use crate::proof_of_space::verifier::{create_challenges, verify};
+pub use crate::proof_of_space::pospace::{prepare_params, N, CHALLENGE_COUNT, RESPONSE_COUNT};
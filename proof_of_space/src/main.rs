@@ -1,7 +0,0 @@
-pub mod circuit;
-pub mod mimc;
-pub mod proof_of_space;
-
-fn main() {
-    println!("Hello, world!");
-}
@@ -0,0 +1,32 @@
+//! 封装、解封装实验，结果写入 src/proof_of_storage/data 下的文件
+//!
+//! cargo run --release --features experiments --example postorage_experiment -- [postorage | pipeline]
+use proof_of_storage::experiments::{test_postorage, test_pipeline};
+use proof_of_storage::preset;
+
+fn main() {
+    if std::env::args().nth(1).as_deref() == Some("pipeline") {
+        test_pipeline();
+        return;
+    }
+
+    let should_save_run_data = false;
+
+    // 参数设置
+    // 原地 unseal
+    // seal = true, unseal = true, else = false, run
+
+    // 并行 unseal single
+    // 先 seal = true, else = false, run
+    // 再 unseal_single = true, else = false, run
+
+    let should_seal = false;
+    let should_unseal = false;
+    let should_challenge_leaves = false;
+    let should_unseal_single = true;
+
+    let params = preset("1m").unwrap();
+    let parallel_num = 0;
+
+    test_postorage(params, should_save_run_data, should_seal, should_unseal, should_challenge_leaves, should_unseal_single, parallel_num).unwrap();
+}
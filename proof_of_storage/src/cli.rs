@@ -0,0 +1,305 @@
+use std::fs;
+
+use clap::{Parser, Subcommand};
+use serde::{Serialize, Deserialize};
+
+use proof_of_storage::{Sealer, Unsealer, SectorHeader, Progress, resume_seal, Challenge, StorageProof, Verifier, PosError, Result, PosPara, DEFAULT_PRESET, SECTOR_VERSION, load_params, preset, read_header_from};
+
+#[derive(Parser, Debug)]
+#[command(name = "postorage", version, about = "Seal sectors and prove they are still stored")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Seal <input> into a new sector file <output>
+    Seal {
+        input: String,
+        output: String,
+        /// Prover id, 32 bytes in hex
+        #[arg(long, value_parser = parse_hex32)]
+        prover_id: [u8; 32],
+        /// Sector number of the new sector
+        #[arg(long, default_value_t = 0)]
+        sector_num: u64,
+        /// Ticket supplied by the verifier, 32 bytes in hex
+        #[arg(long, value_parser = parse_hex32)]
+        ticket: [u8; 32],
+        /// TOML or JSON parameter file; defaults to the built-in preset
+        #[arg(long, conflicts_with = "preset")]
+        config: Option<String>,
+        /// Built-in parameter preset (1m, 4m, 16m, 64m)
+        #[arg(long)]
+        preset: Option<String>,
+        /// Save a checkpoint next to <output> every N sealed blocks so `resume` can continue after a crash
+        #[arg(long)]
+        checkpoint_every: Option<usize>,
+    },
+    /// Continue sealing <sealed> from its checkpoint
+    Resume {
+        sealed: String,
+        /// Save a checkpoint every N sealed blocks
+        #[arg(long, default_value_t = 64)]
+        checkpoint_every: usize,
+    },
+    /// Recover the original data of a sealed sector into <output>
+    Unseal {
+        sealed: String,
+        output: String,
+        /// Unseal blocks in batches with N worker threads
+        #[arg(long)]
+        threads: Option<usize>,
+    },
+    /// Recover blocks [from, to) of a sealed sector into <output>
+    UnsealRange {
+        sealed: String,
+        output: String,
+        #[arg(long)]
+        from: usize,
+        #[arg(long)]
+        to: usize,
+    },
+    /// Pick random blocks of a sealed sector and write the challenge to <output>
+    Challenge {
+        sealed: String,
+        output: String,
+        /// Number of challenged blocks; defaults to leaves_to_prove_count
+        #[arg(long)]
+        count: Option<usize>,
+        /// Derive the challenge from this public seed (32 bytes in hex) instead of sampling it
+        #[arg(long, value_parser = parse_hex32)]
+        seed: Option<[u8; 32]>,
+    },
+    /// Answer <challenge> from the sealed sector and write the encoded proof to <output>
+    Prove {
+        sealed: String,
+        challenge: String,
+        output: String,
+    },
+    /// Check <proof> against <challenge> for the sector committed to by --comm-r
+    Verify {
+        challenge: String,
+        proof: String,
+        /// Trusted comm_r of the sector, 32 bytes in hex, as printed by `seal` or `inspect`
+        #[arg(long, value_parser = parse_hex32)]
+        comm_r: [u8; 32],
+        /// Trusted parameter id of the sector, 32 bytes in hex
+        #[arg(long, value_parser = parse_hex32)]
+        params_id: [u8; 32],
+        /// Number of blocks in the sector
+        #[arg(long)]
+        block_cnt: usize,
+        /// Trusted replica id of the sector, 32 bytes in hex; required to check a challenge derived from a seed
+        #[arg(long, value_parser = parse_hex32)]
+        replica_id: Option<[u8; 32]>,
+    },
+    /// Print the header of a sealed sector
+    Inspect {
+        sealed: String,
+    },
+}
+
+pub fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Command::Seal { input, output, prover_id, sector_num, ticket, config, preset, checkpoint_every } => cmd_seal(&input, &output, prover_id, sector_num, &ticket, config.as_deref(), preset.as_deref(), checkpoint_every),
+        Command::Resume { sealed, checkpoint_every } => print_commitments(&resume_seal(&sealed, checkpoint_every, &Progress::none())?),
+        Command::Unseal { sealed, output, threads } => match threads {
+            Some(threads) => Unsealer::open(&sealed)?.unseal_to_parallel(&output, threads),
+            None => Unsealer::open(&sealed)?.unseal_to(&output),
+        },
+        Command::UnsealRange { sealed, output, from, to } => Unsealer::open(&sealed)?.unseal_range_to(from, to, &output),
+        Command::Challenge { sealed, output, count, seed } => cmd_challenge(&sealed, &output, count, seed.as_ref()),
+        Command::Prove { sealed, challenge, output } => cmd_prove(&sealed, &challenge, &output),
+        Command::Verify { challenge, proof, comm_r, params_id, block_cnt, replica_id } => {
+            let verifier = Verifier::new(params_id, comm_r, block_cnt);
+            cmd_verify(&challenge, &proof, &match replica_id {
+                Some(replica_id) => verifier.with_replica_id(replica_id),
+                None => verifier,
+            })
+        },
+        Command::Inspect { sealed } => cmd_inspect(&sealed),
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex32(s: &str) -> std::result::Result<[u8; 32], String> {
+    if s.len() != 64 || !s.is_ascii() {
+        return Err(format!("expected 64 hex digits, got {:?}", s));
+    }
+    let mut res = [0u8; 32];
+    for (i, byte) in res.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).map_err(|err| format!("bad hex {:?}: {}", s, err))?;
+    }
+    Ok(res)
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &str) -> Result<T> {
+    let content = fs::read_to_string(path)?;
+    serde_json::from_str(&content).map_err(|err| PosError::InvalidParams(format!("bad json in {}: {}", path, err)))
+}
+
+fn write_json<T: Serialize>(path: &str, value: &T) -> Result<()> {
+    let content = serde_json::to_string_pretty(value).map_err(|err| PosError::InvalidParams(err.to_string()))?;
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_seal(input: &str, output: &str, prover_id: [u8; 32], sector_num: u64, ticket: &[u8; 32], config: Option<&str>, preset_name: Option<&str>, checkpoint_every: Option<usize>) -> Result<()> {
+    let params: PosPara = match config {
+        Some(path) => load_params(path)?,
+        None => preset(preset_name.unwrap_or(DEFAULT_PRESET))?,
+    };
+    let sealer = Sealer::new(params, prover_id)?;
+    let header = match checkpoint_every {
+        Some(checkpoint_every) => sealer.seal_resumable(input, output, sector_num, ticket, checkpoint_every)?,
+        None => sealer.seal(input, output, sector_num, ticket)?,
+    };
+    print_commitments(&header)
+}
+
+fn print_commitments(header: &SectorHeader) -> Result<()> {
+    println!("replica id: {}", to_hex(&header.replica_id().id()));
+    println!("comm_d: {}", to_hex(&header.comm_d));
+    println!("comm_r: {}", to_hex(&header.comm_r));
+    println!("params id: {}", to_hex(&header.params.id()));
+    println!("block count: {}", header.params.block_count());
+    Ok(())
+}
+
+fn cmd_challenge(sealed: &str, output: &str, count: Option<usize>, seed: Option<&[u8; 32]>) -> Result<()> {
+    let unsealer = Unsealer::open(sealed)?;
+    let header = unsealer.header();
+    let verifier = Verifier::from_header(header);
+    let count = count.unwrap_or(header.params.leaves_to_prove_count);
+    let challenge = match seed {
+        Some(seed) => verifier.challenge_from_seed(seed, count)?,
+        None => verifier.challenge(count)?,
+    };
+    write_json(output, &challenge)
+}
+
+fn cmd_prove(sealed: &str, challenge: &str, output: &str) -> Result<()> {
+    let challenge: Challenge = read_json(challenge)?;
+    fs::write(output, StorageProof::generate(sealed, &challenge)?.to_bytes()?)?;
+    Ok(())
+}
+
+fn cmd_verify(challenge: &str, proof: &str, verifier: &Verifier) -> Result<()> {
+    //! verifier 由验证者保存的 comm_r 等构造，不能取自挑战文件：挑战与证明都可能由证明者给出，
+    //! 带种子的挑战由 verifier 重新导出被挑战的二级数据块
+    let challenge: Challenge = read_json(challenge)?;
+    let proof = StorageProof::from_bytes(&fs::read(proof)?)?;
+    verifier.verify(&challenge, &proof)?;
+    println!("proof ok: {} blocks verified against comm_r {}", challenge.indices.len(), to_hex(&challenge.comm_r));
+    Ok(())
+}
+
+fn cmd_inspect(sealed: &str) -> Result<()> {
+    let header = read_header_from(sealed)?;
+    let params = serde_json::to_string_pretty(&header.params).map_err(|err| PosError::InvalidParams(err.to_string()))?;
+
+    println!("version: {}", SECTOR_VERSION);
+    println!("sealed: {}", header.sealed);
+    println!("prover id: {}", to_hex(&header.prover_id));
+    println!("sector num: {}", header.sector_num);
+    println!("ticket: {}", to_hex(&header.ticket));
+    println!("replica id: {}", to_hex(&header.replica_id().id()));
+    println!("data len: {}", header.data_len);
+    println!("comm_d: {}", to_hex(&header.comm_d));
+    println!("comm_r: {}", to_hex(&header.comm_r));
+    println!("params id: {}", to_hex(&header.params.id()));
+    println!("block count: {}", header.params.block_count());
+    println!("params: {}", params);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_args(args: &[&str]) -> Result<()> {
+        run(Cli::try_parse_from([&["postorage"], args].concat()).unwrap())
+    }
+
+    #[test]
+    fn test_cli_round_trip() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+        let (config, origin, sealed, unsealed, range, challenge, proof) = (
+            path("pos_test_cli.toml"), path("pos_test_cli_origin"), path("pos_test_cli_sealed"), path("pos_test_cli_unsealed"),
+            path("pos_test_cli_range"), path("pos_test_cli_challenge.json"), path("pos_test_cli_proof"),
+        );
+
+        fs::write(&config, "data_l = 2016\nblock_l = 252\nbig_block_l = 2016\nseal_rounds = 1\nvde_rounds = 2\n").unwrap();
+        let data: Vec<u8> = (0..2016).map(|i| (i * 7 % 251) as u8).collect();
+        fs::write(&origin, &data).unwrap();
+
+        let (prover_id, ticket) = (to_hex(&[1u8; 32]), to_hex(&[2u8; 32]));
+        assert!(Cli::try_parse_from(["postorage", "seal", &origin, &sealed, "--prover-id", "01", "--ticket", &ticket]).is_err());
+        run_args(&["seal", &origin, &sealed, "--config", &config, "--prover-id", &prover_id, "--sector-num", "5", "--ticket", &ticket, "--checkpoint-every", "3"]).unwrap();
+        run_args(&["resume", &sealed]).unwrap();
+        let resumable = fs::read(&sealed).unwrap();
+        run_args(&["seal", &origin, &sealed, "--config", &config, "--prover-id", &prover_id, "--sector-num", "5", "--ticket", &ticket]).unwrap();
+        assert_eq!(fs::read(&sealed).unwrap(), resumable);
+        assert_eq!(read_header_from(&sealed).unwrap().replica_id().sector_num, 5);
+        run_args(&["inspect", &sealed]).unwrap();
+        run_args(&["unseal", &sealed, &unsealed]).unwrap();
+        assert_eq!(fs::read(&unsealed).unwrap(), data);
+        run_args(&["unseal-range", &sealed, &range, "--from", "2", "--to", "5"]).unwrap();
+        assert_eq!(fs::read(&range).unwrap(), data[2 * 252..5 * 252]);
+        assert!(run_args(&["unseal-range", &sealed, &range, "--from", "5", "--to", "9"]).is_err());
+
+        // 验证者保存的 comm_r、参数编号与二级数据块个数
+        let header = read_header_from(&sealed).unwrap();
+        let (comm_r, params_id, block_cnt) = (to_hex(&header.comm_r), to_hex(&header.params.id()), header.params.block_count().to_string());
+        let trusted = ["--comm-r", comm_r.as_str(), "--params-id", params_id.as_str(), "--block-cnt", block_cnt.as_str()];
+        let verify = |challenge: &str, proof: &str| run_args(&[&["verify", challenge, proof], &trusted[..]].concat());
+        assert!(Cli::try_parse_from(["postorage", "verify", &challenge, &proof]).is_err());
+
+        run_args(&["challenge", &sealed, &challenge, "--count", "3"]).unwrap();
+        run_args(&["prove", &sealed, &challenge, &proof]).unwrap();
+        verify(&challenge, &proof).unwrap();
+
+        // 挑战文件中的 comm_r 被篡改时，即使证明与之一致也验证失败
+        let mut forged: Challenge = read_json(&challenge).unwrap();
+        forged.comm_r = [9u8; 32];
+        write_json(&challenge, &forged).unwrap();
+        assert!(matches!(verify(&challenge, &proof), Err(PosError::InvalidProof(_))));
+
+        // 同一个种子导出的挑战相同，验证带种子的挑战还需要验证者保存的副本编号
+        run_args(&["challenge", &sealed, &challenge, "--count", "3", "--seed", &ticket]).unwrap();
+        let seeded = fs::read(&challenge).unwrap();
+        run_args(&["challenge", &sealed, &challenge, "--count", "3", "--seed", &ticket]).unwrap();
+        assert_eq!(fs::read(&challenge).unwrap(), seeded);
+        run_args(&["prove", &sealed, &challenge, &proof]).unwrap();
+        assert!(matches!(verify(&challenge, &proof), Err(PosError::InvalidProof(_))));
+        let replica_id = to_hex(&header.replica_id().id());
+        let verify = |challenge: &str, proof: &str| run_args(&[&["verify", challenge, proof, "--replica-id", &replica_id], &trusted[..]].concat());
+        verify(&challenge, &proof).unwrap();
+
+        // 证明者改为自行挑选的二级数据块并如实应答，重新导出的挑战与之不一致
+        let mut picked: Challenge = read_json(&challenge).unwrap();
+        picked.indices = (0..8).filter(|i| !picked.indices.contains(i)).take(3).collect();
+        write_json(&challenge, &picked).unwrap();
+        run_args(&["prove", &sealed, &challenge, &proof]).unwrap();
+        assert!(matches!(verify(&challenge, &proof), Err(PosError::InvalidProof(_))));
+        fs::write(&challenge, &seeded).unwrap();
+        run_args(&["prove", &sealed, &challenge, &proof]).unwrap();
+
+        // 篡改证明中的数据块后验证失败
+        let mut bad = StorageProof::from_bytes(&fs::read(&proof).unwrap()).unwrap();
+        bad.blocks[0][0] ^= 1;
+        fs::write(&proof, bad.to_bytes().unwrap()).unwrap();
+        assert!(matches!(verify(&challenge, &proof), Err(PosError::InvalidProof(_))));
+
+        for p in [config, origin, sealed, unsealed, range, challenge, proof] {
+            fs::remove_file(p).unwrap();
+        }
+    }
+}
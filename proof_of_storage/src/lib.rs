@@ -0,0 +1,34 @@
+//! 存储证明：封装原始数据、由封装数据恢复原始数据，并应答验证者的挑战
+//!
+//! 对外接口为 Sealer、Unsealer、Challenge、StorageProof 与 Verifier，参数见 PosPara 与 PosConfig。
+//! 复制证明（验证者只持有 comm_d 与 comm_r）见 PorepVerifier。
+//! 时空证明（按窗口周期性挑战扇区）见 PostScheduler；同时封装多个扇区见 SealScheduler。
+pub mod vde;
+
+pub mod zk_vde;
+
+mod proof_of_storage;
+
+pub use crate::proof_of_storage::api::{Sealer, Unsealer, Challenge, Verifier, read_range};
+pub use crate::proof_of_storage::cache::{BlockCache, DEFAULT_CACHE_BYTES};
+pub use crate::proof_of_storage::checkpoint::{SealCheckpoint, CHECKPOINT_VERSION, seal_resumable, resume_seal};
+pub use crate::proof_of_storage::config::{PosConfig, DEFAULT_PRESET, load_params};
+pub use crate::proof_of_storage::depend::{DependCount, LongDependency, ShortDependency};
+pub use crate::proof_of_storage::error::{PosError, Result};
+pub use crate::proof_of_storage::mmap::SectorAccess;
+pub use crate::proof_of_storage::params::{PosPara, PosParaBuilder, PRESETS, preset};
+pub use crate::proof_of_storage::porep::{PorepChallenge, PorepVerifier};
+pub use crate::proof_of_storage::post::{Beacon, Clock, SystemClock, SimulatedClock, PostConfig, PostSector, PostWindow, PostScheduler, WindowRecord, WindowStatus};
+pub use crate::proof_of_storage::progress::{Phase, ProgressEvent, ProgressObserver, CancelToken, Progress};
+pub use crate::proof_of_storage::proof::{StorageProof, PROOF_VERSION};
+pub use crate::proof_of_storage::repair::reseal_block;
+pub use crate::proof_of_storage::replica::ReplicaId;
+pub use crate::proof_of_storage::scheduler::{SealJob, SealLimits, JobStatus, JobReport, SealScheduler};
+pub use crate::proof_of_storage::sector::{Commitments, SectorHeader, SECTOR_VERSION, read_header_from};
+pub use crate::proof_of_storage::stats::{SealStats, UnsealStats, StatsRecord, StatsFormat, StatsWriter, prefixed_header, open_stats_file};
+pub use crate::proof_of_storage::stream::{seal_stream, seal_stream_with_progress, unseal_stream, unseal_stream_with_progress, unseal_parallel, unseal_parallel_with_progress};
+pub use crate::vde::mode::{Vde, VdeMode, Sloth};
+
+/// 论文实验使用的驱动程序，见 examples/postorage_experiment.rs
+#[cfg(feature = "experiments")]
+pub use crate::proof_of_storage::postorage as experiments;
@@ -1,9 +1,10 @@
-pub mod vde;
+mod cli;
 
-pub mod zk_vde;
-
-pub mod proof_of_storage;
+use clap::Parser;
 
 fn main() {
-    println!("Hello, world!");
+    if let Err(err) = cli::run(cli::Cli::parse()) {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
 }
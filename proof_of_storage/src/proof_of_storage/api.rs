@@ -0,0 +1,511 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Write, BufReader, BufWriter};
+use std::sync::Mutex;
+
+use rs_merkle::{MerkleTree, algorithms::Sha256};
+use serde::{Serialize, Deserialize};
+
+use super::cache::{BlockCache, DEFAULT_CACHE_BYTES};
+use super::checkpoint::seal_resumable;
+use super::common::{blake3_hash, read_file};
+use super::error::{PosError, Result};
+use super::merkle_tree::{generate_merkle_tree_from_data, generate_merkle_proof};
+use super::params::PosPara;
+use super::proof::StorageProof;
+use super::progress::Progress;
+use super::prover::{unseal, copy_and_compress};
+use super::sector::{SectorHeader, data_offset, read_header, read_sealed_leaves};
+use super::stream::{seal_stream_with_progress, unseal_stream_with_progress, unseal_parallel_with_progress, strip_padding};
+use super::verifier::{create_challenges, derive_challenges, batch_unseal_prepare, batch_unseal, batch_unseal_parallel};
+
+/// 每次从封装文件中读出并解封装的二级数据块个数
+const UNSEAL_BATCH: usize = 16;
+
+/// 证明者 prover_id 封装原始文件，封装数据及解封装所需的参数写入同一个扇区文件
+pub struct Sealer {
+    params: PosPara,
+    prover_id: [u8; 32],
+    progress: Progress,
+}
+
+impl Sealer {
+    pub fn new(params: PosPara, prover_id: [u8; 32]) -> Result<Sealer> {
+        params.validate()?;
+        Ok(Sealer { params, prover_id, progress: Progress::none() })
+    }
+
+    pub fn with_progress(mut self, progress: Progress) -> Sealer {
+        //! 之后的封装通知 progress，并可由对应的 CancelToken 取消
+        self.progress = progress;
+        self
+    }
+
+    pub fn params(&self) -> &PosPara {
+        &self.params
+    }
+
+    pub fn prover_id(&self) -> &[u8; 32] {
+        &self.prover_id
+    }
+
+    pub fn seal(&self, input: &str, output: &str, sector_num: u64, ticket: &[u8; 32]) -> Result<SectorHeader> {
+        //! 以验证者给出的 ticket 封装 input 并写入扇区 sector_num 的文件 output，input 的长度不能超过 params.data_l
+        self.check_input_len(input)?;
+        let mut input_file = BufReader::new(File::open(input)?);
+        let mut output_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(output)?;
+        seal_stream_with_progress(&mut input_file, &mut output_file, &self.params, &self.prover_id, sector_num, ticket, &self.progress)
+    }
+
+    pub fn seal_resumable(&self, input: &str, output: &str, sector_num: u64, ticket: &[u8; 32], checkpoint_every: usize) -> Result<SectorHeader> {
+        //! 与 seal 相同，每封装 checkpoint_every 个二级数据块在 output 旁保存一次检查点，中断后用 resume_seal 继续
+        self.check_input_len(input)?;
+        let mut input_file = BufReader::new(File::open(input)?);
+        seal_resumable(&mut input_file, output, &self.params, &self.prover_id, sector_num, ticket, checkpoint_every, &self.progress)
+    }
+
+    fn check_input_len(&self, input: &str) -> Result<()> {
+        let input_len = fs::metadata(input)?.len();
+        if input_len > self.params.data_l as u64 {
+            return Err(PosError::InvalidParams(format!("{} is {} bytes, params allow at most {} bytes", input, input_len, self.params.data_l)));
+        }
+        Ok(())
+    }
+}
+
+/// 从扇区文件恢复原始数据，只依赖扇区文件本身
+pub struct Unsealer {
+    path: String,
+    header: SectorHeader,
+    progress: Progress,
+    // 各次 unseal_blocks 共用，扇区文件在 Unsealer 存在期间保持封装状态
+    cache: Mutex<BlockCache>,
+}
+
+impl Unsealer {
+    pub fn open(path: &str) -> Result<Unsealer> {
+        let mut file = OpenOptions::new()
+        .read(true)
+        .open(path)?;
+        let header = read_header(&mut file)?;
+        header.check_sealed()?;
+        Ok(Unsealer { path: path.to_string(), header, progress: Progress::none(), cache: Mutex::new(BlockCache::new(DEFAULT_CACHE_BYTES)) })
+    }
+
+    pub fn with_progress(mut self, progress: Progress) -> Unsealer {
+        //! 之后的解封装通知 progress，并可由对应的 CancelToken 取消
+        self.progress = progress;
+        self
+    }
+
+    pub fn with_cache_bytes(mut self, budget: usize) -> Unsealer {
+        //! 缓存封装后二级数据块的内存上限（字节），默认为 DEFAULT_CACHE_BYTES，0 表示不缓存
+        self.cache = Mutex::new(BlockCache::new(budget));
+        self
+    }
+
+    pub fn cache_counters(&self) -> (u64, u64) {
+        //! 读取封装后二级数据块时命中、未命中缓存的次数
+        let cache = self.cache.lock().unwrap();
+        (cache.hits(), cache.misses())
+    }
+
+    pub fn header(&self) -> &SectorHeader {
+        &self.header
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.header.params.block_count()
+    }
+
+    pub fn unseal_blocks(&self, from: usize, to: usize) -> Result<Vec<u8>> {
+        //! 解封装第 [from, to) 个二级数据块，返回去掉 pad 及末尾补 0 后的原始数据
+        self.unseal_blocks_with(from, to, 0)
+    }
+
+    pub fn unseal_blocks_parallel(&self, from: usize, to: usize, parallel_num: usize) -> Result<Vec<u8>> {
+        //! 与 unseal_blocks 相同，二级数据块由 parallel_num 个线程并行解封装
+        if parallel_num == 0 {
+            return Err(PosError::InvalidParams("parallel_num must be greater than 0".to_string()));
+        }
+        self.unseal_blocks_with(from, to, parallel_num)
+    }
+
+    pub fn read_range(&self, offset: u64, len: usize) -> Result<Vec<u8>> {
+        //! 读出原始数据中从 offset 开始的 len 字节，只解封装覆盖这段数据的二级数据块
+        let data_len = self.header.data_len;
+        if offset.checked_add(len as u64).is_none_or(|end| end > data_len) {
+            return Err(PosError::InvalidParams(format!("range of {} bytes at offset {} exceeds data length {}", len, offset, data_len)));
+        }
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        // 每个二级数据块 pad 前有 block_l 字节原始数据
+        let (offset, block_l) = (offset as usize, self.header.params.block_l);
+        let (from, to) = (offset / block_l, (offset + len).div_ceil(block_l));
+        let data = self.unseal_blocks(from, to)?;
+        let begin = offset - from * block_l;
+        Ok(data[begin..begin + len].to_vec())
+    }
+
+    pub fn unseal_to(&self, output: &str) -> Result<()> {
+        //! 解封装全部数据并写入 output
+        let mut sealed_file = BufReader::new(File::open(&self.path)?);
+        let mut output_file = BufWriter::new(File::create(output)?);
+        unseal_stream_with_progress(&mut sealed_file, &mut output_file, &self.header.params, &self.progress)
+    }
+
+    pub fn unseal_to_parallel(&self, output: &str, threads: usize) -> Result<()> {
+        //! 与 unseal_to 相同，二级数据块分批由 threads 个线程并行解封装
+        unseal_parallel_with_progress(&self.path, output, &self.header.params, threads, &self.progress)
+    }
+
+    pub fn unseal_range_to(&self, from: usize, to: usize, output: &str) -> Result<()> {
+        //! 解封装第 [from, to) 个二级数据块并写入 output，每次只在内存中保留 UNSEAL_BATCH 个二级数据块
+        self.check_range(from, to)?;
+        let mut output_file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(output)?;
+
+        for begin in (from..to).step_by(UNSEAL_BATCH) {
+            let end = usize::min(begin + UNSEAL_BATCH, to);
+            output_file.write_all(&self.unseal_blocks(begin, end)?)?;
+        }
+        Ok(())
+    }
+
+    pub fn unseal_in_place(self, output: &str) -> Result<()> {
+        //! 顺序原地解封装后去掉 pad 写入 output，完成后扇区文件不再是封装状态
+        let params = &self.header.params;
+        unseal(params, &self.path, &self.progress)?;
+        copy_and_compress(&self.path, output, params.data_l, params.unit_l, params.unit_pl)
+    }
+
+    fn check_range(&self, from: usize, to: usize) -> Result<()> {
+        if from >= to || to > self.block_count() {
+            return Err(PosError::InvalidParams(format!("block range [{}, {}) is empty or exceeds block count {}", from, to, self.block_count())));
+        }
+        Ok(())
+    }
+
+    fn unseal_blocks_with(&self, from: usize, to: usize, parallel_num: usize) -> Result<Vec<u8>> {
+        self.check_range(from, to)?;
+        let params = &self.header.params;
+        let (vde_key, iv) = (self.header.vde_key()?, self.header.iv());
+
+        let (blocks_idx, mut blocks, before_block_ids, depend_blocks) = batch_unseal_prepare(&self.path, from * params.block_pl, to * params.block_pl, params, &mut self.cache.lock().unwrap())?;
+        let unsealed_blocks = {
+            if parallel_num == 0 {
+                batch_unseal(params, &blocks_idx, &mut blocks, &before_block_ids, &depend_blocks, &vde_key, &iv, &self.progress)?
+            }
+            else {
+                batch_unseal_parallel(params, blocks_idx.clone(), blocks, before_block_ids, depend_blocks, &vde_key, &iv, parallel_num, &self.progress)?
+            }
+        };
+
+        let mut res = Vec::with_capacity((to - from) * params.block_l);
+        for (i, block) in unsealed_blocks.iter().enumerate() {
+            res.append(&mut strip_padding(params, blocks_idx[i], block, self.header.data_len as usize)?);
+        }
+        Ok(res)
+    }
+}
+
+pub fn read_range(sealed: &str, offset: u64, len: usize) -> Result<Vec<u8>> {
+    //! 从扇区文件 sealed 读出原始数据中从 offset 开始的 len 字节，见 Unsealer::read_range
+    Unsealer::open(sealed)?.read_range(offset, len)
+}
+
+/// 验证者发出的挑战：被挑战的二级数据块编号及挑战时的参数编号与 comm_r
+///
+/// seed: 由公开种子导出的挑战记录种子，验证时重新导出 indices；验证者随机挑战时为 None
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Challenge {
+    pub params_id: [u8; 32],
+    pub comm_r: [u8; 32],
+    pub block_cnt: usize,
+    pub indices: Vec<usize>,
+    #[serde(default)]
+    pub seed: Option<[u8; 32]>,
+}
+
+impl StorageProof {
+    pub fn generate(sealed: &str, challenge: &Challenge) -> Result<StorageProof> {
+        //! 证明者由扇区文件应答挑战：被挑战的封装后二级数据块及其 merkle 路径，扇区数据与挑战不一致时返回错误
+        //!
+        //! merkle 路径由扇区文件中保存的 comm_r 叶子生成，只读取被挑战的二级数据块
+        let mut file = OpenOptions::new()
+        .read(true)
+        .open(sealed)?;
+        let header = read_header(&mut file)?;
+        header.check_sealed()?;
+
+        let params = &header.params;
+        let block_cnt = params.block_count();
+        if challenge.params_id != params.id() || challenge.comm_r != header.comm_r || challenge.block_cnt != block_cnt {
+            return Err(PosError::InvalidParams("challenge was issued for another sector".to_string()));
+        }
+
+        let (blocks_id, merkle_tree) = read_comm_r_tree(&mut file, &header)?;
+        let mut blocks = vec![];
+        for &idx2 in &challenge.indices {
+            if idx2 >= block_cnt {
+                return Err(PosError::InvalidParams(format!("challenged block {} out of range, block count is {}", idx2, block_cnt)));
+            }
+            blocks.push(read_opened_block(&mut file, params, idx2, &blocks_id)?);
+        }
+
+        let mut opened_indices = challenge.indices.clone();
+        opened_indices.sort_unstable();
+        Ok(StorageProof {
+            params_id: params.id(),
+            indices: challenge.indices.clone(),
+            blocks,
+            depend_indices: vec![],
+            depend_blocks: vec![],
+            comm_r_proof: generate_merkle_proof(&opened_indices, &merkle_tree)?.to_bytes(),
+            comm_d_proof: None,
+        })
+    }
+}
+
+pub fn read_comm_r_tree(file: &mut File, header: &SectorHeader) -> Result<(Vec<Vec<u8>>, MerkleTree<Sha256>)> {
+    //! 读出封装时保存的 comm_r 叶子（各封装后二级数据块的哈希值）并重建 merkle 树，根与文件头部的 comm_r 不一致时返回错误
+    let blocks_id = read_sealed_leaves(file, &header.params)?;
+    let (_, merkle_tree, comm_r) = generate_merkle_tree_from_data(&blocks_id)?;
+    if comm_r != header.comm_r {
+        return Err(PosError::CorruptData("stored leaves do not match comm_r in the header".to_string()));
+    }
+    Ok((blocks_id, merkle_tree))
+}
+
+pub fn read_opened_block(file: &mut File, params: &PosPara, idx2: usize, blocks_id: &Vec<Vec<u8>>) -> Result<Vec<u8>> {
+    //! 读出证明中打开的第 idx2 个封装后二级数据块，与 comm_r 的叶子不一致时返回错误
+    let block = read_file(file, data_offset(idx2 * params.block_pl), params.block_pl)?;
+    if blake3_hash(&block) != blocks_id[idx2] {
+        return Err(PosError::CorruptData(format!("sealed block {} does not match its comm_r leaf", idx2)));
+    }
+    Ok(block)
+}
+
+/// 验证者只需保存参数编号、comm_r 与二级数据块个数，由种子导出挑战时还需保存副本编号
+pub struct Verifier {
+    params_id: [u8; 32],
+    comm_r: [u8; 32],
+    block_cnt: usize,
+    replica_id: Option<[u8; 32]>,
+}
+
+impl Verifier {
+    pub fn new(params_id: [u8; 32], comm_r: [u8; 32], block_cnt: usize) -> Verifier {
+        Verifier { params_id, comm_r, block_cnt, replica_id: None }
+    }
+
+    pub fn with_replica_id(mut self, replica_id: [u8; 32]) -> Verifier {
+        //! 验证者保存的副本编号（ReplicaId::id），用于导出及重新导出由种子决定的挑战
+        self.replica_id = Some(replica_id);
+        self
+    }
+
+    pub fn from_header(header: &SectorHeader) -> Verifier {
+        Verifier::new(header.params.id(), header.comm_r, header.params.block_count()).with_replica_id(header.replica_id().id())
+    }
+
+    pub fn challenge(&self, count: usize) -> Result<Challenge> {
+        //! 随机挑战 count 个不同的二级数据块
+        self.check_count(count)?;
+        Ok(Challenge {
+            params_id: self.params_id,
+            comm_r: self.comm_r,
+            block_cnt: self.block_cnt,
+            indices: create_challenges(count, (0, self.block_cnt))?,
+            seed: None,
+        })
+    }
+
+    pub fn challenge_from_seed(&self, seed: &[u8; 32], count: usize) -> Result<Challenge> {
+        //! 由公开种子（如区块哈希）、comm_r 与验证者保存的副本编号导出 count 个不同的挑战
+        //!
+        //! 证明者可以自行导出挑战并发布证明，任何人事后用同一个种子重新导出挑战即可验证
+        self.check_count(count)?;
+        let replica_id = self.replica_id.ok_or_else(|| PosError::InvalidParams("verifier has no replica id to derive challenges from a seed".to_string()))?;
+        Ok(Challenge {
+            params_id: self.params_id,
+            comm_r: self.comm_r,
+            block_cnt: self.block_cnt,
+            indices: derive_challenges(seed, &self.comm_r, &replica_id, count, (0, self.block_cnt))?,
+            seed: Some(*seed),
+        })
+    }
+
+    fn check_count(&self, count: usize) -> Result<()> {
+        if count == 0 || count > self.block_cnt {
+            return Err(PosError::InvalidParams(format!("challenge count {} must be in 1..={}", count, self.block_cnt)));
+        }
+        Ok(())
+    }
+
+    pub fn verify(&self, challenge: &Challenge, proof: &StorageProof) -> Result<()> {
+        //! 挑战带有种子时用验证者保存的副本编号重新导出被挑战的二级数据块，与挑战中的不一致则拒绝，
+        //! 证明者不能自行挑选容易应答的二级数据块
+        if challenge.params_id != self.params_id || challenge.comm_r != self.comm_r || challenge.block_cnt != self.block_cnt {
+            return Err(PosError::InvalidProof("challenge was issued for another sector".to_string()));
+        }
+        if let Some(seed) = &challenge.seed {
+            let replica_id = self.replica_id.ok_or_else(|| PosError::InvalidProof("verifier has no replica id to re-derive a seeded challenge".to_string()))?;
+            self.check_count(challenge.indices.len()).map_err(|err| PosError::InvalidProof(err.to_string()))?;
+            if derive_challenges(seed, &self.comm_r, &replica_id, challenge.indices.len(), (0, self.block_cnt))? != challenge.indices {
+                return Err(PosError::InvalidProof("challenged blocks were not derived from the seed".to_string()));
+            }
+        }
+        proof.check_shape(&self.params_id, &challenge.indices)?;
+        proof.verify_comm_r(self.comm_r, self.block_cnt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proof_of_storage::verifier::create_random_file;
+    use crate::proof_of_storage::sector::sealed_leaves_offset;
+    use crate::proof_of_storage::test_util::{test_params, temp_path, seal_sector};
+
+    #[test]
+    fn test_api_round_trip() {
+        let params = test_params();
+        let (origin, sealed, unsealed) = (temp_path("pos_test_api_origin"), temp_path("pos_test_api_sealed"), temp_path("pos_test_api_unsealed"));
+        create_random_file(&origin, params.data_l).unwrap();
+        let data = fs::read(&origin).unwrap();
+
+        let header = Sealer::new(params.clone(), [1u8; 32]).unwrap().seal(&origin, &sealed, 0, &[2u8; 32]).unwrap();
+        assert_eq!(header.params, params);
+
+        let unsealer = Unsealer::open(&sealed).unwrap();
+        assert_eq!(unsealer.unseal_blocks(1, 3).unwrap(), data[params.block_l..3 * params.block_l]);
+        assert_eq!(unsealer.unseal_blocks_parallel(0, 8, 3).unwrap(), data);
+        assert!(unsealer.unseal_blocks(3, 9).is_err());
+
+        let verifier = Verifier::from_header(&header);
+        let challenge = verifier.challenge(3).unwrap();
+        let mut proof = StorageProof::generate(&sealed, &challenge).unwrap();
+        verifier.verify(&challenge, &proof).unwrap();
+
+        // 证明者由公开种子自行导出挑战，验证者用同一个种子重新导出后验证
+        let seeded = verifier.challenge_from_seed(&[7u8; 32], 8).unwrap();
+        assert_eq!(seeded.indices, (0..8).collect::<Vec<usize>>());
+        let seeded = verifier.challenge_from_seed(&[7u8; 32], 3).unwrap();
+        assert_eq!(seeded.seed, Some([7u8; 32]));
+        let seeded_proof = StorageProof::generate(&sealed, &seeded).unwrap();
+        // 证明编码后可以在另一个进程中验证
+        let decoded = StorageProof::from_bytes(&seeded_proof.to_bytes().unwrap()).unwrap();
+        verifier.verify(&verifier.challenge_from_seed(&[7u8; 32], 3).unwrap(), &decoded).unwrap();
+        let other_params = Verifier::new(PosPara { vde_rounds: 3, ..params.clone() }.id(), header.comm_r, 8).with_replica_id(header.replica_id().id());
+        assert!(other_params.verify(&Challenge { params_id: PosPara { vde_rounds: 3, ..params.clone() }.id(), ..seeded.clone() }, &decoded).is_err());
+        assert!(verifier.challenge_from_seed(&[7u8; 32], 9).is_err());
+        assert!(Verifier::new(params.id(), header.comm_r, 8).challenge_from_seed(&[7u8; 32], 3).is_err());
+
+        // 证明者声称由种子导出、实际自行挑选的二级数据块，即使证明与之一致也被拒绝
+        let picked: Vec<usize> = (0..8).filter(|i| !seeded.indices.contains(i)).take(3).collect();
+        let forged = Challenge { indices: picked, ..seeded.clone() };
+        let forged_proof = StorageProof::generate(&sealed, &forged).unwrap();
+        assert!(matches!(verifier.verify(&forged, &forged_proof), Err(PosError::InvalidProof(_))));
+        assert!(matches!(verifier.verify(&Challenge { seed: Some([8u8; 32]), ..seeded.clone() }, &decoded), Err(PosError::InvalidProof(_))));
+        // 没有副本编号的验证者无法重新导出，拒绝带种子的挑战
+        assert!(matches!(Verifier::new(params.id(), header.comm_r, 8).verify(&seeded, &decoded), Err(PosError::InvalidProof(_))));
+        proof.blocks[1][0] ^= 1;
+        assert!(matches!(verifier.verify(&challenge, &proof), Err(PosError::InvalidProof(_))));
+
+        // 证明只读取保存的 comm_r 叶子及被挑战的二级数据块：其余二级数据块损坏不影响应答，
+        // 被挑战的二级数据块或叶子损坏时拒绝生成证明
+        let content = fs::read(&sealed).unwrap();
+        let damage = |pos: usize| {
+            let mut damaged = content.clone();
+            damaged[pos] ^= 1;
+            fs::write(&sealed, &damaged).unwrap();
+        };
+        let unchallenged = (0..8).find(|i| !challenge.indices.contains(i)).unwrap();
+        damage(data_offset(unchallenged * params.block_pl));
+        verifier.verify(&challenge, &StorageProof::generate(&sealed, &challenge).unwrap()).unwrap();
+        damage(data_offset(challenge.indices[0] * params.block_pl));
+        assert!(matches!(StorageProof::generate(&sealed, &challenge), Err(PosError::CorruptData(_))));
+        damage(sealed_leaves_offset(&params));
+        assert!(matches!(StorageProof::generate(&sealed, &challenge), Err(PosError::CorruptData(_))));
+        fs::write(&sealed, &content).unwrap();
+
+        unsealer.unseal_in_place(&unsealed).unwrap();
+        assert_eq!(fs::read(&unsealed).unwrap(), data);
+        assert!(Unsealer::open(&sealed).is_err());
+
+        for p in [origin, sealed, unsealed] {
+            fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_api_short_input() {
+        let params = test_params();
+        let (origin, sealed, unsealed) = (temp_path("pos_test_api_short_origin"), temp_path("pos_test_api_short_sealed"), temp_path("pos_test_api_short_unsealed"));
+        let data: Vec<u8> = (0..1000).map(|i| (i % 255 + 1) as u8).collect();
+        fs::write(&origin, &data).unwrap();
+
+        let header = Sealer::new(params.clone(), [1u8; 32]).unwrap().seal(&origin, &sealed, 0, &[2u8; 32]).unwrap();
+        assert_eq!(header.data_len, 1000);
+
+        let unsealer = Unsealer::open(&sealed).unwrap();
+        assert_eq!(unsealer.block_count(), 8);
+        assert_eq!(unsealer.unseal_blocks(0, 8).unwrap(), data);
+        assert_eq!(unsealer.unseal_blocks(3, 4).unwrap(), data[3 * params.block_l..]);
+        assert!(unsealer.unseal_blocks(5, 8).unwrap().is_empty());
+        unsealer.unseal_to(&unsealed).unwrap();
+        assert_eq!(fs::read(&unsealed).unwrap(), data);
+
+        unsealer.unseal_in_place(&unsealed).unwrap();
+        assert_eq!(fs::read(&unsealed).unwrap(), data);
+
+        fs::write(&origin, vec![1u8; params.data_l + 1]).unwrap();
+        assert!(matches!(Sealer::new(params, [1u8; 32]).unwrap().seal(&origin, &sealed, 0, &[2u8; 32]), Err(PosError::InvalidParams(_))));
+
+        for p in [origin, sealed, unsealed] {
+            fs::remove_file(p).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_read_range() {
+        let params = test_params();
+        let sealed = temp_path("pos_test_read_range_sealed");
+        let data: Vec<u8> = (0..1000).map(|i| (i % 251 + 3) as u8).collect();
+        seal_sector(&params, &data, 0, &sealed);
+        let unsealer = Unsealer::open(&sealed).unwrap();
+
+        // 跨越一级、二级数据块的边界
+        for (offset, len) in [(5, 1), (params.block_l - 3, 7), (params.unit_l * 2 + 1, params.block_l + 10), (params.block_l, 2 * params.block_l)] {
+            assert_eq!(unsealer.read_range(offset as u64, len).unwrap(), data[offset..offset + len]);
+        }
+        assert_eq!(read_range(&sealed, 200, 400).unwrap(), data[200..600]);
+
+        // 恰好结束于原始数据末尾，最后一个二级数据块只有部分原始数据
+        for (offset, len) in [(0, 1000), (990, 10), (999, 1), (3 * params.block_l, 1000 - 3 * params.block_l)] {
+            assert_eq!(unsealer.read_range(offset as u64, len).unwrap(), data[offset..]);
+        }
+
+        // 超出原始数据末尾
+        for (offset, len) in [(990, 11), (1000, 1), (0, params.data_l), (u64::MAX, 2)] {
+            assert!(matches!(unsealer.read_range(offset, len), Err(PosError::InvalidParams(_))));
+        }
+
+        // 长度为 0 时不解封装，偏移至多为原始数据长度
+        for offset in [0, 500, 1000] {
+            assert!(unsealer.read_range(offset, 0).unwrap().is_empty());
+        }
+        assert!(matches!(unsealer.read_range(1001, 0), Err(PosError::InvalidParams(_))));
+
+        fs::remove_file(sealed).unwrap();
+    }
+}
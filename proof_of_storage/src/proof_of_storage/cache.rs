@@ -0,0 +1,162 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::error::Result;
+
+/// 封装时默认的缓存大小（字节）
+pub const DEFAULT_CACHE_BYTES: usize = 64 << 20;
+
+/// 最近使用的封装后二级数据块（一级数据块形式），按二级数据块编号缓存
+///
+/// 总大小不超过 budget 字节，超出时淘汰最久未使用的二级数据块；budget 为 0 时不缓存。
+/// 只缓存已经封装的二级数据块，一个缓存只能用于同一个扇区，且扇区在使用期间不能被原地解封装
+pub struct BlockCache {
+    budget: usize,
+    used: usize,
+    // 二级数据块编号 -> (二级数据块, 最近一次使用的序号)
+    blocks: HashMap<usize, (Vec<Vec<u8>>, u64)>,
+    // 最近一次使用的序号 -> 二级数据块编号，最小的最先淘汰
+    order: BTreeMap<u64, usize>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl BlockCache {
+    pub fn new(budget: usize) -> BlockCache {
+        BlockCache { budget, used: 0, blocks: HashMap::new(), order: BTreeMap::new(), tick: 0, hits: 0, misses: 0 }
+    }
+
+    pub fn disabled() -> BlockCache {
+        BlockCache::new(0)
+    }
+
+    pub fn get(&mut self, idx2: usize) -> Option<Vec<Vec<u8>>> {
+        let tick = self.next_tick();
+        match self.blocks.get_mut(&idx2) {
+            Some((block, last_used)) => {
+                self.order.remove(last_used);
+                self.order.insert(tick, idx2);
+                *last_used = tick;
+                self.hits += 1;
+                Some(block.clone())
+            },
+            None => {
+                self.misses += 1;
+                None
+            },
+        }
+    }
+
+    pub fn get_or_load<F: FnOnce() -> Result<Vec<Vec<u8>>>>(&mut self, idx2: usize, load: F) -> Result<Vec<Vec<u8>>> {
+        //! 未命中时调用 load 读出第 idx2 个二级数据块并放入缓存
+        if let Some(block) = self.get(idx2) {
+            return Ok(block);
+        }
+        let block = load()?;
+        self.insert(idx2, block.clone());
+        Ok(block)
+    }
+
+    pub fn insert(&mut self, idx2: usize, block: Vec<Vec<u8>>) {
+        //! 放入第 idx2 个二级数据块，替换已有的同一编号；大于 budget 的二级数据块不缓存
+        self.remove(idx2);
+        let size = block_size(&block);
+        if size > self.budget {
+            return;
+        }
+        while self.used + size > self.budget {
+            let (_, oldest) = self.order.pop_first().unwrap();
+            let (old_block, _) = self.blocks.remove(&oldest).unwrap();
+            self.used -= block_size(&old_block);
+        }
+        let tick = self.next_tick();
+        self.order.insert(tick, idx2);
+        self.blocks.insert(idx2, (block, tick));
+        self.used += size;
+    }
+
+    pub fn remove(&mut self, idx2: usize) {
+        if let Some((block, last_used)) = self.blocks.remove(&idx2) {
+            self.order.remove(&last_used);
+            self.used -= block_size(&block);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        //! 清空缓存的二级数据块，命中与未命中次数保留
+        self.blocks.clear();
+        self.order.clear();
+        self.used = 0;
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+fn block_size(block: &Vec<Vec<u8>>) -> usize {
+    block.iter().map(|unit| unit.len()).sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_block_cache() {
+        let block = |b: u8| vec![vec![b; 4]; 2];
+        // 可以放下 3 个 8 字节的二级数据块
+        let mut cache = BlockCache::new(30);
+        for idx2 in 0..3 {
+            cache.insert(idx2, block(idx2 as u8));
+        }
+        assert_eq!((cache.len(), cache.used()), (3, 24));
+
+        // 访问 0 之后，最久未使用的是 1
+        assert_eq!(cache.get(0), Some(block(0)));
+        cache.insert(3, block(3));
+        assert_eq!(cache.get(1), None);
+        assert_eq!(cache.get(2), Some(block(2)));
+        assert_eq!((cache.hits(), cache.misses()), (2, 1));
+
+        let loaded = cache.get_or_load(1, || Ok(block(1))).unwrap();
+        assert_eq!(loaded, block(1));
+        assert_eq!(cache.get_or_load(1, || panic!("should hit")).unwrap(), block(1));
+        assert_eq!(cache.get(0), None);
+        assert_eq!(cache.used(), 24);
+
+        // 同一编号替换，不重复计入大小
+        cache.insert(1, vec![vec![9; 4]]);
+        assert_eq!((cache.len(), cache.used()), (3, 20));
+
+        let mut disabled = BlockCache::disabled();
+        disabled.insert(0, block(0));
+        assert!(disabled.is_empty());
+        assert!(disabled.get_or_load(0, || Err(crate::proof_of_storage::error::PosError::CorruptData("x".to_string()))).is_err());
+    }
+}
@@ -0,0 +1,249 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read};
+
+use rug::Integer;
+use serde::{Serialize, Deserialize};
+
+use super::cache::{BlockCache, DEFAULT_CACHE_BYTES};
+use super::common::{com_units, blake3_hash};
+use super::error::{PosError, Result};
+use super::params::PosPara;
+use super::progress::{Phase, Progress};
+use super::prover::prepare_params;
+use super::replica::ReplicaId;
+use super::sector::{SectorHeader, read_header, write_header, read_origin_leaves};
+use super::stream::{pad_stream, seal_blocks, finish_seal, read_sealed_block, read_depend_blocks, unseal_one};
+
+/// 检查点文件格式版本，位于检查点文件的第一个字节
+pub const CHECKPOINT_VERSION: u8 = 1;
+
+/// 封装进度：前 blocks_id.len() 个二级数据块已原地封装，blocks_id 为它们的哈希值链
+///
+/// 保存在扇区文件旁的 <sealed>.ckpt 中，封装完成后删除
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SealCheckpoint {
+    pub params_id: [u8; 32],
+    pub replica_id: ReplicaId,
+    pub data_len: u64,
+    pub blocks_id: Vec<Vec<u8>>,
+}
+
+impl SealCheckpoint {
+    pub fn path(sealed: &str) -> String {
+        format!("{}.ckpt", sealed)
+    }
+
+    pub fn load(sealed: &str) -> Result<SealCheckpoint> {
+        let bytes = fs::read(SealCheckpoint::path(sealed))?;
+        match bytes.split_first() {
+            Some((&CHECKPOINT_VERSION, body)) => bincode::deserialize(body).map_err(|err| PosError::CorruptData(format!("bad checkpoint: {}", err))),
+            Some((version, _)) => Err(PosError::CorruptData(format!("unsupported checkpoint version {}, expected {}", version, CHECKPOINT_VERSION))),
+            None => Err(PosError::CorruptData("empty checkpoint".to_string())),
+        }
+    }
+
+    pub fn save(&self, sealed: &str) -> Result<()> {
+        //! 先写临时文件再改名，进程在写入途中退出时旧的检查点保持完整
+        let mut bytes = vec![CHECKPOINT_VERSION];
+        bytes.append(&mut bincode::serialize(self).map_err(|err| PosError::InvalidParams(err.to_string()))?);
+        let path = SealCheckpoint::path(sealed);
+        let tmp_path = format!("{}.tmp", path);
+        fs::write(&tmp_path, bytes)?;
+        fs::rename(&tmp_path, &path)?;
+        Ok(())
+    }
+
+    pub fn sealed_blocks(&self) -> usize {
+        self.blocks_id.len()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn seal_resumable<R: Read>(reader: &mut R, sealed: &str, params: &PosPara, prover_id: &[u8; 32], sector_num: u64, ticket: &[u8; 32], checkpoint_every: usize, progress: &Progress) -> Result<SectorHeader> {
+    //! 与 seal_stream 相同，每封装 checkpoint_every 个二级数据块保存一次检查点，进程中断后可用 resume_seal 继续
+    //!
+    //! 封装阶段被取消时先保存检查点再返回 Cancelled，之后同样可以用 resume_seal 继续
+    seal_resumable_with(reader, sealed, params, prover_id, sector_num, ticket, checkpoint_every, progress, |_| Ok(()))
+}
+
+pub fn resume_seal(sealed: &str, checkpoint_every: usize, progress: &Progress) -> Result<SectorHeader> {
+    //! 从检查点继续封装 sealed
+    //!
+    //! 检查点记录的哈希值链须与文件中已封装的二级数据块一致；检查点之后已经写回的二级数据块
+    //! 通过在内存中解封装并与 comm_d 的叶子比较来识别，写到一半的二级数据块返回 CorruptData
+    resume_seal_with(sealed, checkpoint_every, progress, |_| Ok(()))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn seal_resumable_with<R: Read, F: FnMut(usize) -> Result<()>>(reader: &mut R, sealed: &str, params: &PosPara, prover_id: &[u8; 32], sector_num: u64, ticket: &[u8; 32], checkpoint_every: usize, progress: &Progress, hook: F) -> Result<SectorHeader> {
+    params.validate()?;
+    check_every(checkpoint_every)?;
+    let vde_key = prepare_params(params.unit_pl)?;
+    let mut file = OpenOptions::new()
+    .read(true)
+    .write(true)
+    .create(true)
+    .truncate(true)
+    .open(sealed)?;
+
+    let (data_len, comm_d) = pad_stream(reader, &mut file, params, progress)?;
+    let replica = ReplicaId::new(*prover_id, sector_num, *ticket, comm_d);
+    // 未完成封装的文件头部：sealed 为 false，comm_r 暂为 0
+    let placeholder = SectorHeader {
+        params: params.clone(),
+        prime: vde_key.to_string(),
+        prover_id: *prover_id,
+        sector_num,
+        ticket: *ticket,
+        data_len,
+        comm_d,
+        comm_r: [0u8; 32],
+        sealed: false,
+    };
+    write_header(&mut file, &placeholder)?;
+
+    let checkpoint = SealCheckpoint { params_id: params.id(), replica_id: replica, data_len, blocks_id: vec![] };
+    checkpoint.save(sealed)?;
+    continue_seal(&mut file, sealed, params, &vde_key, checkpoint, checkpoint_every, progress, hook)
+}
+
+fn resume_seal_with<F: FnMut(usize) -> Result<()>>(sealed: &str, checkpoint_every: usize, progress: &Progress, hook: F) -> Result<SectorHeader> {
+    check_every(checkpoint_every)?;
+    let mut file = OpenOptions::new()
+    .read(true)
+    .write(true)
+    .open(sealed)?;
+    let header = read_header(&mut file)?;
+    if header.sealed {
+        // 写入文件头部之后、删除检查点之前中断
+        remove_checkpoint(sealed)?;
+        return Ok(header);
+    }
+
+    let params = &header.params;
+    params.validate()?;
+    let vde_key = header.vde_key()?;
+    let mut checkpoint = SealCheckpoint::load(sealed)?;
+    if checkpoint.params_id != params.id() || checkpoint.replica_id != header.replica_id() || checkpoint.data_len != header.data_len {
+        return Err(PosError::CorruptData("checkpoint belongs to another sector".to_string()));
+    }
+    if checkpoint.sealed_blocks() > params.block_count() {
+        return Err(PosError::CorruptData(format!("checkpoint records {} sealed blocks, sector has {}", checkpoint.sealed_blocks(), params.block_count())));
+    }
+    for (idx2, block_id) in checkpoint.blocks_id.iter().enumerate() {
+        if blake3_hash(&read_sealed_block(&mut file, params, idx2)?) != *block_id {
+            return Err(PosError::CorruptData(format!("block {} does not match the checkpoint", idx2)));
+        }
+    }
+
+    // 检查点之后可能还有已经封装并写回的二级数据块
+    let origin_leaves = read_origin_leaves(&mut file, params)?;
+    let iv = checkpoint.replica_id.iv();
+    while checkpoint.sealed_blocks() < params.block_count() {
+        let idx2 = checkpoint.sealed_blocks();
+        let block = read_sealed_block(&mut file, params, idx2)?;
+        if blake3_hash(&block) == origin_leaves[idx2] {
+            break;
+        }
+        let before_block_id = if idx2 == 0 { vec![] } else { checkpoint.blocks_id[idx2 - 1].clone() };
+        let depend_blocks = read_depend_blocks(&mut file, params, idx2, &before_block_id, &mut BlockCache::disabled())?;
+        let unsealed = unseal_one(params, idx2, &block, &before_block_id, depend_blocks, &vde_key, &iv)?;
+        if blake3_hash(&com_units(&unsealed[0])) != origin_leaves[idx2] {
+            return Err(PosError::CorruptData(format!("block {} is neither original nor sealed data, it may have been partially written", idx2)));
+        }
+        checkpoint.blocks_id.push(blake3_hash(&block));
+    }
+    continue_seal(&mut file, sealed, params, &vde_key, checkpoint, checkpoint_every, progress, hook)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn continue_seal<F: FnMut(usize) -> Result<()>>(file: &mut fs::File, sealed: &str, params: &PosPara, vde_key: &Integer, checkpoint: SealCheckpoint, checkpoint_every: usize, progress: &Progress, mut hook: F) -> Result<SectorHeader> {
+    //! 从检查点记录的位置继续封装，完成后写入文件头部并删除检查点
+    let SealCheckpoint { params_id, replica_id, data_len, mut blocks_id } = checkpoint;
+    let block_cnt = params.block_count();
+    let seal_start = progress.start(Phase::Seal, block_cnt);
+    let mut cache = BlockCache::new(DEFAULT_CACHE_BYTES);
+    seal_blocks(file, params, vde_key, &replica_id.iv(), &mut blocks_id, &mut cache, |idx2, blocks_id| {
+        let done = progress.block_done(Phase::Seal, idx2, idx2 + 1, block_cnt);
+        if (idx2 + 1) % checkpoint_every == 0 || done.is_err() {
+            SealCheckpoint { params_id, replica_id, data_len, blocks_id: blocks_id.clone() }.save(sealed)?;
+        }
+        done?;
+        hook(idx2)
+    })?;
+    progress.finish(Phase::Seal, seal_start);
+
+    let header = finish_seal(file, params, vde_key, &replica_id, data_len, &blocks_id)?;
+    remove_checkpoint(sealed)?;
+    Ok(header)
+}
+
+fn check_every(checkpoint_every: usize) -> Result<()> {
+    if checkpoint_every == 0 {
+        return Err(PosError::InvalidParams("checkpoint_every must be greater than 0".to_string()));
+    }
+    Ok(())
+}
+
+fn remove_checkpoint(sealed: &str) -> Result<()> {
+    match fs::remove_file(SealCheckpoint::path(sealed)) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(PosError::Io(err)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use std::path::Path;
+    use crate::proof_of_storage::progress::ProgressEvent;
+    use crate::proof_of_storage::test_util::{test_params, temp_path, seal_sector};
+
+    #[test]
+    fn test_resume_seal() {
+        let params = test_params();
+        let (reference, sealed) = (&temp_path("pos_test_ckpt_reference"), &temp_path("pos_test_ckpt_sealed"));
+        let data: Vec<u8> = (0..1900).map(|i| (i * 31 % 256) as u8).collect();
+        let args = (&[1u8; 32], 4, &[2u8; 32]);
+
+        let expected = seal_sector(&params, &data, args.1, reference);
+        let expected_bytes = fs::read(reference).unwrap();
+
+        // 封装完第 4 个二级数据块后中断，最近的检查点只记录了前 4 个二级数据块
+        let crash = |idx2: usize| if idx2 == 4 { Err(PosError::InvalidParams("crash".to_string())) } else { Ok(()) };
+        assert!(seal_resumable_with(&mut Cursor::new(&data), sealed, &params, args.0, args.1, args.2, 2, &Progress::none(), crash).is_err());
+        assert_eq!(SealCheckpoint::load(sealed).unwrap().sealed_blocks(), 4);
+        assert!(!read_header(&mut fs::File::open(sealed).unwrap()).unwrap().sealed);
+
+        // 再次在第 6 个二级数据块中断，之后恢复到完成
+        let crash = |idx2: usize| if idx2 == 6 { Err(PosError::InvalidParams("crash".to_string())) } else { Ok(()) };
+        assert!(resume_seal_with(sealed, 3, &Progress::none(), crash).is_err());
+        assert_eq!(resume_seal(sealed, 3, &Progress::none()).unwrap(), expected);
+        assert_eq!(fs::read(sealed).unwrap(), expected_bytes);
+        assert!(!Path::new(&SealCheckpoint::path(sealed)).exists());
+        // 已经完成的扇区再次恢复时直接返回
+        assert_eq!(resume_seal(sealed, 3, &Progress::none()).unwrap(), expected);
+
+        // 检查点与扇区文件不一致时拒绝恢复
+        assert!(seal_resumable_with(&mut Cursor::new(&data), sealed, &params, args.0, args.1, args.2, 2, &Progress::none(), |idx2| if idx2 == 3 { Err(PosError::InvalidParams("crash".to_string())) } else { Ok(()) }).is_err());
+        let mut checkpoint = SealCheckpoint::load(sealed).unwrap();
+        checkpoint.blocks_id[1][0] ^= 1;
+        checkpoint.save(sealed).unwrap();
+        assert!(matches!(resume_seal(sealed, 2, &Progress::none()), Err(PosError::CorruptData(_))));
+        fs::remove_file(SealCheckpoint::path(sealed)).unwrap();
+        assert!(matches!(resume_seal(sealed, 2, &Progress::none()), Err(PosError::Io(_))));
+        assert!(matches!(resume_seal(sealed, 0, &Progress::none()), Err(PosError::InvalidParams(_))));
+
+        // 被取消时保存检查点，之后可以继续
+        let (progress, token) = Progress::new(|_: &ProgressEvent| {});
+        let cancel = |idx2: usize| { if idx2 == 5 { token.cancel(); } Ok(()) };
+        assert!(matches!(seal_resumable_with(&mut Cursor::new(&data), sealed, &params, args.0, args.1, args.2, 4, &progress, cancel), Err(PosError::Cancelled)));
+        assert_eq!(SealCheckpoint::load(sealed).unwrap().sealed_blocks(), 7);
+        assert_eq!(resume_seal(sealed, 4, &Progress::none()).unwrap(), expected);
+        assert_eq!(fs::read(sealed).unwrap(), expected_bytes);
+
+        fs::remove_file(reference).unwrap();
+        fs::remove_file(sealed).unwrap();
+    }
+}
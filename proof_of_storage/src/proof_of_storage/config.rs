@@ -0,0 +1,165 @@
+use std::fs;
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use super::depend::{LongDependency, ShortDependency};
+use super::error::{PosError, Result};
+use super::params::{PosPara, PosParaBuilder, preset};
+use crate::vde::mode::VdeMode;
+
+/// 未指定 preset 时使用的内置参数
+pub const DEFAULT_PRESET: &str = "1m";
+
+/// 配置文件内容：在内置参数的基础上覆盖部分字段
+///
+/// ```toml
+/// preset = "16m"
+/// seal_rounds = 3
+/// long_depend = { odd = { fixed = 3 } }
+/// ```
+///
+/// pad 后的长度可以省略，由 unit_l 等推导；若给出则必须与推导结果一致。
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct PosConfig {
+    pub preset: Option<String>,
+
+    pub data_l: Option<usize>,
+    pub unit_l: Option<usize>,
+    pub block_l: Option<usize>,
+    pub big_block_l: Option<usize>,
+
+    pub unit_pl: Option<usize>,
+    pub block_pl: Option<usize>,
+    pub big_block_pl: Option<usize>,
+
+    pub seal_rounds: Option<usize>,
+    pub vde_rounds: Option<usize>,
+    pub vde_mode: Option<VdeMode>,
+
+    pub long_depend: Option<LongDependency>,
+    pub short_depend: Option<ShortDependency>,
+
+    pub leaves_to_prove_count: Option<usize>,
+}
+
+impl PosConfig {
+    pub fn from_toml_str(s: &str) -> Result<PosConfig> {
+        toml::from_str(s).map_err(|err| PosError::InvalidParams(format!("bad toml config: {}", err)))
+    }
+
+    pub fn from_json_str(s: &str) -> Result<PosConfig> {
+        serde_json::from_str(s).map_err(|err| PosError::InvalidParams(format!("bad json config: {}", err)))
+    }
+
+    pub fn load(path: &str) -> Result<PosConfig> {
+        //! 根据扩展名（.toml / .json）选择解析方式
+        let content = fs::read_to_string(path)?;
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => PosConfig::from_toml_str(&content),
+            Some("json") => PosConfig::from_json_str(&content),
+            _ => Err(PosError::InvalidParams(format!("unknown config format {:?}, expected .toml or .json", path))),
+        }
+    }
+
+    pub fn build(&self) -> Result<PosPara> {
+        //! 先取 preset 对应的内置参数，再用配置中给出的字段覆盖
+        let base = preset(self.preset.as_deref().unwrap_or(DEFAULT_PRESET))?;
+        let mut builder = PosParaBuilder::from(&base);
+
+        if let Some(data_l) = self.data_l {
+            builder = builder.data_l(data_l);
+        }
+        if let Some(unit_l) = self.unit_l {
+            builder = builder.unit_l(unit_l);
+        }
+        if let Some(block_l) = self.block_l {
+            builder = builder.block_l(block_l);
+        }
+        if let Some(big_block_l) = self.big_block_l {
+            builder = builder.big_block_l(big_block_l);
+        }
+        if let Some(seal_rounds) = self.seal_rounds {
+            builder = builder.seal_rounds(seal_rounds);
+        }
+        if let Some(vde_rounds) = self.vde_rounds {
+            builder = builder.vde_rounds(vde_rounds);
+        }
+        if let Some(vde_mode) = self.vde_mode {
+            builder = builder.vde_mode(vde_mode);
+        }
+        if let Some(long_depend) = self.long_depend {
+            builder = builder.long_depend(long_depend);
+        }
+        if let Some(short_depend) = self.short_depend {
+            builder = builder.short_depend(short_depend);
+        }
+        if let Some(count) = self.leaves_to_prove_count {
+            builder = builder.leaves_to_prove_count(count);
+        }
+
+        let mut params = builder.build()?;
+        params.unit_pl = self.unit_pl.unwrap_or(params.unit_pl);
+        params.block_pl = self.block_pl.unwrap_or(params.block_pl);
+        params.big_block_pl = self.big_block_pl.unwrap_or(params.big_block_pl);
+        params.validate()?;
+        Ok(params)
+    }
+}
+
+pub fn load_params(path: &str) -> Result<PosPara> {
+    //! 从 toml / json 配置文件读取参数
+    PosConfig::load(path)?.build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proof_of_storage::depend::DependCount;
+
+    #[test]
+    fn test_partial_override() {
+        let config = PosConfig::from_toml_str("preset = \"4m\"\nseal_rounds = 3\nlong_depend = { odd = { fixed = 2 } }\nshort_depend = { symmetric = 4 }\n").unwrap();
+        let params = config.build().unwrap();
+        let base = preset("4m").unwrap();
+        assert_eq!(params.seal_rounds, 3);
+        assert_eq!(params.long_depend, LongDependency::Odd(DependCount::Fixed(2)));
+        assert_eq!(params.short_depend, ShortDependency::Symmetric(4));
+        assert_eq!((params.data_l, params.block_l, params.vde_rounds), (base.data_l, base.block_l, base.vde_rounds));
+
+        let config = PosConfig::from_json_str(r#"{"block_l": 252, "big_block_l": 2016}"#).unwrap();
+        let params = config.build().unwrap();
+        assert_eq!((params.block_l, params.block_pl, params.big_block_pl), (252, 256, 2048));
+        assert_eq!(params.data_l, preset(DEFAULT_PRESET).unwrap().data_l);
+
+        // PosPara 本身也是合法的配置文件
+        let params = preset("16m").unwrap();
+        let json = serde_json::to_string(&params).unwrap();
+        assert_eq!(PosConfig::from_json_str(&json).unwrap().build().unwrap(), params);
+        let toml = toml::to_string(&params).unwrap();
+        assert_eq!(PosConfig::from_toml_str(&toml).unwrap().build().unwrap(), params);
+    }
+
+    #[test]
+    fn test_bad_config() {
+        let bad = [
+            PosConfig::from_toml_str("preset = \"2m\""),
+            PosConfig::from_toml_str("seal_round = 3"),
+            PosConfig::from_toml_str("block_l = 100"),
+            PosConfig::from_toml_str("unit_pl = 63"),
+            PosConfig::from_json_str(r#"{"short_depend": {"alternating": 4}}"#),
+            PosConfig::from_json_str(r#"{"mode_s": 1}"#),
+            PosConfig::from_json_str(r#"{"vde_mode": "unknown"}"#),
+            PosConfig::from_json_str("{"),
+        ];
+        for config in bad {
+            assert!(matches!(config.and_then(|config| config.build()), Err(PosError::InvalidParams(_))));
+        }
+
+        let path = std::env::temp_dir().join("pos_test_config.yaml");
+        std::fs::write(&path, "seal_rounds: 3").unwrap();
+        assert!(matches!(load_params(path.to_str().unwrap()), Err(PosError::InvalidParams(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,6 +1,120 @@
 use blake3;
 
 use std::collections::HashSet;
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+/// 依赖个数策略
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DependCount {
+    /// 依赖个数固定
+    Fixed(usize),
+    /// 依赖个数随编号增长：idx / 10 + 1
+    Growing,
+}
+
+impl DependCount {
+    pub fn count(&self, index: usize) -> usize {
+        match *self {
+            DependCount::Fixed(count) => count,
+            DependCount::Growing => index / 10 + 1,
+        }
+    }
+}
+
+/// 二级数据块之间的长程依赖规则
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LongDependency {
+    /// 由前一个封装后二级数据块的哈希值随机选取，见 long_mode_random
+    Random(DependCount),
+    /// -1, -3, -5, -7...，见 long_mode_1
+    Odd(DependCount),
+    /// -2, -3, -5, -9...，见 long_mode_2；Growing 时与旧的 mode 3 相同，见 long_mode_3
+    PowerOfTwo(DependCount),
+}
+
+impl LongDependency {
+    pub fn indices(&self, num: usize, index: usize, before_block_id: &Vec<u8>) -> Vec<usize> {
+        //! 第 index 个二级数据块依赖的二级数据块编号，所有封装、解封装路径都通过这里选择依赖
+        //!
+        //! num: 二级数据块个数
+        //!
+        //! before_block_id: 前一个封装后二级数据块的哈希值，index = 0 时为空
+        match *self {
+            LongDependency::Random(count) => {
+                if index == 0 {
+                    vec![]
+                }
+                else {
+                    long_mode_random(num, before_block_id, index, count.count(index))
+                }
+            },
+            LongDependency::Odd(count) => long_mode_1(index, count.count(index)),
+            LongDependency::PowerOfTwo(DependCount::Growing) => long_mode_3(index),
+            LongDependency::PowerOfTwo(count) => long_mode_2(index, count.count(index)),
+        }
+    }
+}
+
+/// 二级数据块内部一级数据块之间的短程依赖规则
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortDependency {
+    /// 由前一个一级数据块随机选取，见 short_depend_random
+    Random(usize),
+    /// -1, 1, -3, 3...，见 short_mode_1
+    Symmetric(usize),
+}
+
+impl ShortDependency {
+    pub fn indices(&self, block: &Vec<Vec<u8>>, index: usize) -> Vec<usize> {
+        //! 二级数据块 block 中第 index 个一级数据块依赖的一级数据块编号
+        //!
+        //! 随机规则以第 index - 1 个一级数据块的当前值为种子，解封装时须按倒序进行
+        match *self {
+            ShortDependency::Random(count) => {
+                if index == 0 {
+                    short_depend_random(block.len(), &vec![], index, count)
+                }
+                else {
+                    short_depend_random(block.len(), &block[index - 1], index, count)
+                }
+            },
+            ShortDependency::Symmetric(count) => short_mode_1(block.len(), index, count),
+        }
+    }
+}
+
+impl fmt::Display for DependCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DependCount::Fixed(count) => write!(f, "{}", count),
+            DependCount::Growing => write!(f, "growing"),
+        }
+    }
+}
+
+impl fmt::Display for LongDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LongDependency::Random(count) => write!(f, "random({})", count),
+            LongDependency::Odd(count) => write!(f, "odd({})", count),
+            LongDependency::PowerOfTwo(count) => write!(f, "power_of_two({})", count),
+        }
+    }
+}
+
+impl fmt::Display for ShortDependency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShortDependency::Random(count) => write!(f, "random({})", count),
+            ShortDependency::Symmetric(count) => write!(f, "symmetric({})", count),
+        }
+    }
+}
 
 pub fn long_mode_1(index: usize, count: usize) -> Vec<usize> {
     //! rule: -1-2\*0, -1-2\*1, -1-2\*2, -1-2\*3...
@@ -25,21 +139,23 @@ pub fn long_mode_2(index: usize, count: usize) -> Vec<usize> {
         // dis = 1 + 2^i
         // dis = 2, 3, 5, 9..
         let dis = 1 + usize::pow(2, i.try_into().unwrap());
-        if index >= dis {
-            long_index.push(index - dis);
+        if index < dis {
+            // 之后的距离更大，且 i 过大时 2^i 会溢出
+            break;
         }
+        long_index.push(index - dis);
     }
     long_index
 }
 
 pub fn long_mode_3(index: usize) -> Vec<usize> {
-    // 回溯个数与当前数据块编号有关，=编号%10
+    //! 旧的 mode 3：规则同 long_mode_2，回溯个数为 index / 10（不加 1），已封装的扇区依赖这一序列，不能改动
+    //!
+    //! i 达到 usize::BITS 时 2^i 溢出：旧实现在 release 构建中回绕为 0，得到重复的 index - 1，这里按 release 构建的结果保留
     let mut long_index = vec![];
     let count = index / 10;
     for i in 0..count {
-        // dis = 1 + 2^i
-        // dis = 2, 3, 5, 9..
-        let dis = 1 + usize::pow(2, i.try_into().unwrap());
+        let dis = 1 + usize::wrapping_pow(2, i.try_into().unwrap());
         if index >= dis {
             long_index.push(index - dis);
         }
@@ -119,30 +235,6 @@ pub fn long_mode_random(num: usize, data: &Vec<u8>, index: usize, count: usize)
     // long_index
 }
 
-pub fn long_depend(index: usize, count: usize, mode: usize) -> Vec<usize> {
-    //! Generate indexs of long depended.
-    //! 
-    //! num: the number of all blocks
-    //! 
-    //! index: the index of the current block
-    //! 
-    //! count: the count of depended indexs
-    //! 
-    //! mode: choose the rule
-    if mode == 1 {
-        long_mode_1(index, count)
-    }
-    else if mode == 2 {
-        long_mode_2(index, count)
-    }
-    else if mode == 3 {
-        long_mode_3(index)
-    }
-    else {
-        vec![]
-    }
-}
-
 pub fn short_mode_1(num: usize, index: usize, count: usize) -> Vec<usize> {
     //! rule: -1-2\*0, +1+2\*0, -1-2\*1, +1+2\*1, ...
     //! 
@@ -189,35 +281,6 @@ pub fn short_mode_1(num: usize, index: usize, count: usize) -> Vec<usize> {
     short_index
 }
 
-pub fn short_mode_2(num: usize, index: usize, count: usize) -> Vec<usize> {
-    //! rule: -1-2\^0, -1+2\^1, -1-2\^2, -1+2\^3...
-    //! 
-    //!       -2,     1,      -5,     7...
-    let mut short_index = vec![];
-    let mut flag = false;
-    for i in 0..count {
-        let idx;
-        let dis1 = 1 - usize::pow(2, i.try_into().unwrap());
-        let dis2 = 1 + usize::pow(2, i.try_into().unwrap());
-        if flag == true {
-            idx = index - dis1;
-        }
-        else {
-            idx = index - dis2;
-        }
-        flag = !flag;
-
-        if idx >= num {
-            break;
-        }
-
-        if idx < num {
-            short_index.push(idx);
-        }
-    }
-    short_index
-}
-
 pub fn short_depend_random(num: usize, data: &Vec<u8>, index: usize, count: usize) -> Vec<usize> {
     let mut short_index = vec![];
     let mut c = 0;
@@ -290,28 +353,6 @@ pub fn short_depend_random(num: usize, data: &Vec<u8>, index: usize, count: usiz
 }
 
 
-pub fn short_depend(num: usize, index: usize, count: usize, mode: usize) -> Vec<usize> {
-    //! Generate indexs of short depended.
-    //! 
-    //! num: the number of all blocks
-    //! 
-    //! index: the index of the current block
-    //! 
-    //! count: the count of depended indexs
-    //! 
-    //! mode: choose the rule
-    if mode == 1 {
-        short_mode_1(num, index, count)
-    }
-    else if mode == 2 {
-        short_mode_2(num, index, count)
-    }
-    else {
-        vec![]
-    }
-}
-
-
 #[cfg(test)]
 mod test{
     use super::*;
@@ -334,6 +375,18 @@ mod test{
         assert_eq!(indexs[2], res[2]);
     }
     
+    #[test]
+    fn test_long_mode_3() {
+        // 与旧的 mode 3 逐个比较
+        let growing = LongDependency::PowerOfTwo(DependCount::Growing);
+        assert_eq!(growing.indices(1000, 5, &vec![]), Vec::<usize>::new());
+        assert_eq!(growing.indices(1000, 25, &vec![]), vec![23, 22]);
+        assert_eq!(growing.indices(1000, 100, &vec![]), vec![98, 97, 95, 91, 83, 67, 35]);
+        let res = growing.indices(1000, 700, &vec![]);
+        assert_eq!(res[..10], [698, 697, 695, 691, 683, 667, 635, 571, 443, 187]);
+        assert_eq!(res[10..], [699; 6]);
+    }
+
     #[test]
     fn test_short_mode_1() {
         let indexs = vec![2, 4, 0, 6];
@@ -344,14 +397,15 @@ mod test{
         assert_eq!(indexs[3], res[3]);
     }
 
-    // test failed
-    // #[test]
-    // fn test_short_mode_2() {
-    //     let indexs = vec![3, 6, 0, 12];
-    //     let res = short_mode_2(13, 5, 4);
-    //     assert_eq!(indexs[0], res[0]);
-    //     assert_eq!(indexs[1], res[1]);
-    //     assert_eq!(indexs[2], res[2]);
-    //     assert_eq!(indexs[3], res[3]);
-    // }
+    #[test]
+    fn test_dependency() {
+        assert_eq!(LongDependency::Random(DependCount::Growing).indices(100, 0, &vec![]), Vec::<usize>::new());
+        assert_eq!(LongDependency::Odd(DependCount::Fixed(3)).indices(100, 6, &vec![]), vec![5, 3, 1]);
+        // 依赖个数随编号增长，距离超过编号后停止
+        assert_eq!(LongDependency::PowerOfTwo(DependCount::Fixed(100)).indices(10000, 9000, &vec![]).len(), 14);
+
+        let block = vec![vec![0u8; 4]; 7];
+        assert_eq!(ShortDependency::Symmetric(4).indices(&block, 3), vec![2, 4, 0, 6]);
+        assert!(ShortDependency::Random(3).indices(&block, 3).iter().all(|&idx| idx != 3 && idx < block.len()));
+    }
 }
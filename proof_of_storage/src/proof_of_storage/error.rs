@@ -0,0 +1,49 @@
+use std::fmt;
+use std::io;
+
+/// 封装、解封装与验证过程中可能出现的错误
+#[derive(Debug)]
+pub enum PosError {
+    /// 文件读写失败
+    Io(io::Error),
+    /// 参数不合法或与数据不匹配
+    InvalidParams(String),
+    /// 封装后的数据已损坏（长度不足、格式错误等）
+    CorruptData(String),
+    /// 第 block 个二级数据块中第 unit 个一级数据块验证失败
+    VerifyFailed { block: usize, unit: usize },
+    /// merkle 证明无法解析或验证失败
+    InvalidProof(String),
+    /// 调用者通过 CancelToken 取消了封装或解封装
+    Cancelled,
+}
+
+pub type Result<T> = std::result::Result<T, PosError>;
+
+impl fmt::Display for PosError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PosError::Io(err) => write!(f, "io error: {}", err),
+            PosError::InvalidParams(msg) => write!(f, "invalid params: {}", msg),
+            PosError::CorruptData(msg) => write!(f, "corrupt sealed data: {}", msg),
+            PosError::VerifyFailed { block, unit } => write!(f, "verification failed at block {}, unit {}", block, unit),
+            PosError::InvalidProof(msg) => write!(f, "invalid proof: {}", msg),
+            PosError::Cancelled => write!(f, "cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for PosError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PosError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for PosError {
+    fn from(err: io::Error) -> Self {
+        PosError::Io(err)
+    }
+}
@@ -1,49 +1,68 @@
+#[cfg(any(test, feature = "experiments"))]
 use std::fs::OpenOptions;
 use rs_merkle::{MerkleTree, MerkleProof, Hasher, algorithms::Sha256};
 
+#[cfg(any(test, feature = "experiments"))]
 use super::common::read_file;
+use super::error::{PosError, Result};
 
+#[cfg(test)]
 pub const DATA_DIR: [&str; 4] = [r"src", "proof_of_storage", "data", "merkle_tree_data"];
+#[cfg(test)]
 pub const MERKLE_TREE_DIR: [&str; 4] = [r"src", "proof_of_storage", "data", "merkle_tree_result"];
 
-pub fn generate_merkle_tree_from_file(path: &str, data_len: usize, leaf_len: usize) -> (Vec<[u8; 32]>, MerkleTree<Sha256>, [u8; 32]) {
+#[cfg(any(test, feature = "experiments"))]
+pub fn generate_merkle_tree_from_file(path: &str, data_len: usize, leaf_len: usize) -> Result<(Vec<[u8; 32]>, MerkleTree<Sha256>, [u8; 32])> {
     let mut file = OpenOptions::new()
     .read(true)
-    .open(path)
-    .unwrap();
+    .open(path)?;
 
     let mut leaf_values = vec![];
     for i in (0..data_len).step_by(leaf_len) {
-        let buf = read_file(&mut file, i + leaf_len, leaf_len);
+        let buf = read_file(&mut file, i, leaf_len)?;
         leaf_values.push(buf);
     }
-    let leaves: Vec< [u8; 32]> = leaf_values.iter().map(|x| Sha256::hash(x)).collect();
-    let merkle_tree = MerkleTree::<Sha256>::from_leaves(&leaves);
-    let merkle_root = merkle_tree.root().ok_or("can't get the merkle root").unwrap();
-    (leaves, merkle_tree, merkle_root)
+    generate_merkle_tree_from_data(&leaf_values)
 }
 
-pub fn generate_merkle_tree_from_data(leaf_values: &Vec<Vec<u8>>) -> (Vec<[u8; 32]>, MerkleTree<Sha256>, [u8; 32]) {
+pub fn generate_merkle_tree_from_data(leaf_values: &Vec<Vec<u8>>) -> Result<(Vec<[u8; 32]>, MerkleTree<Sha256>, [u8; 32])> {
     let leaves: Vec< [u8; 32]> = leaf_values.iter().map(|x| Sha256::hash(x)).collect();
     let merkle_tree = MerkleTree::<Sha256>::from_leaves(&leaves);
-    let merkle_root = merkle_tree.root().ok_or("can't get the merkle root").unwrap();
-    (leaves, merkle_tree, merkle_root)
+    let merkle_root = merkle_tree.root().ok_or_else(|| PosError::InvalidParams("can't get the merkle root of an empty tree".to_string()))?;
+    Ok((leaves, merkle_tree, merkle_root))
 }
 
-pub fn generate_merkle_proof(indices_to_prove: &[usize], merkle_tree: &MerkleTree<Sha256>) -> MerkleProof<Sha256> {
-    let merkle_proof = merkle_tree.proof(&indices_to_prove);
+pub fn generate_merkle_proof(indices_to_prove: &[usize], merkle_tree: &MerkleTree<Sha256>) -> Result<MerkleProof<Sha256>> {
+    let merkle_proof = merkle_tree.proof(indices_to_prove);
     let proof_bytes = merkle_proof.to_bytes();
-    let proof = MerkleProof::<Sha256>::try_from(proof_bytes).unwrap();
-    proof
+    MerkleProof::<Sha256>::try_from(proof_bytes).map_err(|err| PosError::InvalidProof(err.to_string()))
 }
 
-pub fn verify_merkle_proof(proof: MerkleProof<Sha256>, merkle_root: [u8; 32], indices_to_prove: &[usize], leaves: &Vec<[u8; 32]>) {
+#[cfg(any(test, feature = "experiments"))]
+pub fn verify_merkle_proof(proof: MerkleProof<Sha256>, merkle_root: [u8; 32], indices_to_prove: &[usize], leaves: &Vec<[u8; 32]>) -> Result<()> {
+    //! 验证 indices_to_prove 对应的叶子结点，验证失败时返回 InvalidProof
     let mut leaves_to_prove = vec![];
-    for i in 0..indices_to_prove.len() {
-        let leaf = leaves.get(indices_to_prove[i]).ok_or("can't get leaves to prove").unwrap();
+    for &idx in indices_to_prove {
+        let leaf = leaves.get(idx).ok_or_else(|| PosError::InvalidProof(format!("leaf {} out of range, leaf count is {}", idx, leaves.len())))?;
         leaves_to_prove.push(*leaf);
     }
-    assert!(proof.verify(merkle_root, &indices_to_prove, leaves_to_prove.as_slice(), leaves.len()));
+    if !proof.verify(merkle_root, indices_to_prove, leaves_to_prove.as_slice(), leaves.len()) {
+        return Err(PosError::InvalidProof(format!("merkle path does not match root for leaves {:?}", indices_to_prove)));
+    }
+    Ok(())
+}
+
+pub fn verify_merkle_proof_bytes(proof_bytes: &[u8], merkle_root: [u8; 32], indices_to_prove: &[usize], leaf_values: &Vec<Vec<u8>>, leaves_count: usize) -> Result<()> {
+    //! 只用被挑战的叶子结点原始数据验证序列化后的 merkle 证明，不需要完整的叶子结点集合
+    if leaf_values.len() != indices_to_prove.len() {
+        return Err(PosError::InvalidProof(format!("{} leaves given for {} indices", leaf_values.len(), indices_to_prove.len())));
+    }
+    let proof = MerkleProof::<Sha256>::try_from(proof_bytes.to_vec()).map_err(|err| PosError::InvalidProof(err.to_string()))?;
+    let leaves_to_prove: Vec<[u8; 32]> = leaf_values.iter().map(|x| Sha256::hash(x)).collect();
+    if indices_to_prove.iter().any(|&idx| idx >= leaves_count) || !proof.verify(merkle_root, indices_to_prove, leaves_to_prove.as_slice(), leaves_count) {
+        return Err(PosError::InvalidProof(format!("merkle path does not match root for leaves {:?}", indices_to_prove)));
+    }
+    Ok(())
 }
 
 // pub fn test_merkle_tree_prove_and_verify(path: &str, data_len: usize, leaf_len: usize, leaves_to_prove_count: usize) {
@@ -88,17 +107,17 @@ pub fn test(){
     let mut t3 = 0.0;
     for _ in 0..SAMPLES {
         let start = Instant::now();
-        let (leaves, merkle_tree, merkle_root) = generate_merkle_tree_from_file(&path, DATA_L, LEAVE_L);
+        let (leaves, merkle_tree, merkle_root) = generate_merkle_tree_from_file(&path, DATA_L, LEAVE_L).unwrap();
         t1 += start.elapsed().as_secs_f32();
 
-        let indices_to_prove = create_challenges(COUNT, (0, leaves.len()));
+        let indices_to_prove = create_challenges(COUNT, (0, leaves.len())).unwrap();
         
         let start = Instant::now();
-        let proof = generate_merkle_proof(&indices_to_prove, &merkle_tree);
+        let proof = generate_merkle_proof(&indices_to_prove, &merkle_tree).unwrap();
         t2 += start.elapsed().as_secs_f32();
 
         let start = Instant::now();
-        verify_merkle_proof(proof, merkle_root, &indices_to_prove, &leaves);
+        verify_merkle_proof(proof, merkle_root, &indices_to_prove, &leaves).unwrap();
         t3 += start.elapsed().as_secs_f32();
     }
     t1 = t1 / (SAMPLES as f32);
@@ -0,0 +1,188 @@
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write, Seek, SeekFrom};
+use std::slice::Chunks;
+
+use memmap2::{Mmap, MmapMut};
+
+use super::error::{PosError, Result};
+use super::params::PosPara;
+use super::sector::data_offset;
+
+/// 32 位目标上可以映射的最大文件长度，超过时改用带缓冲的读写
+#[cfg(target_pointer_width = "32")]
+const MMAP_LIMIT: u64 = 1 << 30;
+
+enum Access {
+    Read(Mmap),
+    Write(MmapMut),
+    // buf 保存最近一次读出的二级数据块
+    Buffered { file: File, buf: Vec<u8> },
+}
+
+/// 按二级数据块编号访问扇区文件中的封装数据，读出的二级数据块、一级数据块以借用的切片返回，不重新分配
+///
+/// 默认映射整个扇区文件；32 位目标上文件长度超过 MMAP_LIMIT 时退回带缓冲的读写。
+/// 映射期间扇区文件不能被其他进程截断，文件头部仍通过 read_header / write_header 读写
+pub struct SectorAccess {
+    access: Access,
+    block_pl: usize,
+    unit_pl: usize,
+    block_cnt: usize,
+}
+
+impl SectorAccess {
+    pub fn open(path: &str, params: &PosPara, writable: bool) -> Result<SectorAccess> {
+        //! writable 为 true 时可以写回二级数据块
+        let file = SectorAccess::open_file(path, params, writable)?;
+        #[cfg(target_pointer_width = "32")]
+        if file.metadata()?.len() > MMAP_LIMIT {
+            return Ok(SectorAccess::with_access(SectorAccess::buffered_access(file, params), params));
+        }
+        let access = {
+            if writable {
+                // 安全性：映射期间文件不会被截断，见结构体说明
+                Access::Write(unsafe { MmapMut::map_mut(&file)? })
+            }
+            else {
+                Access::Read(unsafe { Mmap::map(&file)? })
+            }
+        };
+        Ok(SectorAccess::with_access(access, params))
+    }
+
+    pub fn open_buffered(path: &str, params: &PosPara, writable: bool) -> Result<SectorAccess> {
+        //! 不映射文件，每次读写一个二级数据块
+        let file = SectorAccess::open_file(path, params, writable)?;
+        Ok(SectorAccess::with_access(SectorAccess::buffered_access(file, params), params))
+    }
+
+    fn with_access(access: Access, params: &PosPara) -> SectorAccess {
+        SectorAccess { access, block_pl: params.block_pl, unit_pl: params.unit_pl, block_cnt: params.block_count() }
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        !matches!(self.access, Access::Buffered { .. })
+    }
+
+    pub fn block(&mut self, idx2: usize) -> Result<&[u8]> {
+        //! 第 idx2 个封装后二级数据块
+        let range = self.range(idx2)?;
+        match &mut self.access {
+            Access::Read(map) => Ok(&map[range]),
+            Access::Write(map) => Ok(&map[range]),
+            Access::Buffered { file, buf } => {
+                file.seek(SeekFrom::Start(range.start as u64))?;
+                file.read_exact(buf)?;
+                Ok(buf)
+            },
+        }
+    }
+
+    pub fn units(&mut self, idx2: usize) -> Result<Chunks<'_, u8>> {
+        //! 第 idx2 个封装后二级数据块中的一级数据块
+        let unit_pl = self.unit_pl;
+        Ok(self.block(idx2)?.chunks(unit_pl))
+    }
+
+    pub fn unit_vecs(&mut self, idx2: usize) -> Result<Vec<Vec<u8>>> {
+        //! 与 to_units(read_file(..)) 相同，只复制一次
+        Ok(self.units(idx2)?.map(|unit| unit.to_vec()).collect())
+    }
+
+    pub fn write_units(&mut self, idx2: usize, units: &Vec<Vec<u8>>) -> Result<()> {
+        //! 将一级数据块依次写回第 idx2 个二级数据块，不先合并
+        let range = self.range(idx2)?;
+        let len: usize = units.iter().map(|unit| unit.len()).sum();
+        if len != self.block_pl {
+            return Err(PosError::InvalidParams(format!("block {} has {} bytes, expected {}", idx2, len, self.block_pl)));
+        }
+        match &mut self.access {
+            Access::Read(_) => Err(PosError::InvalidParams("sector was opened read-only".to_string())),
+            Access::Write(map) => {
+                let mut begin = range.start;
+                for unit in units {
+                    map[begin..begin + unit.len()].copy_from_slice(unit);
+                    begin += unit.len();
+                }
+                Ok(())
+            },
+            Access::Buffered { file, .. } => {
+                file.seek(SeekFrom::Start(range.start as u64))?;
+                for unit in units {
+                    file.write_all(unit)?;
+                }
+                Ok(())
+            },
+        }
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        //! 将写回的二级数据块同步到文件
+        match &mut self.access {
+            Access::Read(_) => Ok(()),
+            Access::Write(map) => Ok(map.flush()?),
+            Access::Buffered { file, .. } => Ok(file.flush()?),
+        }
+    }
+
+    fn range(&self, idx2: usize) -> Result<std::ops::Range<usize>> {
+        if idx2 >= self.block_cnt {
+            return Err(PosError::InvalidParams(format!("block index {} out of range, block count is {}", idx2, self.block_cnt)));
+        }
+        let begin = data_offset(idx2 * self.block_pl);
+        Ok(begin..begin + self.block_pl)
+    }
+
+    fn open_file(path: &str, params: &PosPara, writable: bool) -> Result<File> {
+        let file = OpenOptions::new()
+        .read(true)
+        .write(writable)
+        .open(path)?;
+        let (len, expected) = (file.metadata()?.len(), data_offset(params.block_count() * params.block_pl));
+        if len < expected as u64 {
+            return Err(PosError::CorruptData(format!("sealed file has {} bytes, expected at least {}", len, expected)));
+        }
+        Ok(file)
+    }
+
+    fn buffered_access(file: File, params: &PosPara) -> Access {
+        Access::Buffered { file, buf: vec![0u8; params.block_pl] }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proof_of_storage::test_util::{test_params_with_blocks, temp_path};
+
+    #[test]
+    fn test_sector_access() {
+        let params = test_params_with_blocks(4);
+        let path = &temp_path("pos_test_sector_access");
+        let data: Vec<u8> = (0..data_offset(4 * params.block_pl)).map(|i| (i % 251) as u8).collect();
+        std::fs::write(path, &data).unwrap();
+
+        let block = |idx2: usize| data[data_offset(idx2 * params.block_pl)..data_offset((idx2 + 1) * params.block_pl)].to_vec();
+        let new_units = vec![vec![7u8; params.unit_pl]; params.block_pl / params.unit_pl];
+        for mapped in [true, false] {
+            let mut sector = if mapped { SectorAccess::open(path, &params, true).unwrap() } else { SectorAccess::open_buffered(path, &params, true).unwrap() };
+            assert_eq!(sector.is_mapped(), mapped);
+            assert_eq!(sector.block(2).unwrap(), &block(2)[..]);
+            assert_eq!(sector.units(1).unwrap().next().unwrap(), &block(1)[..params.unit_pl]);
+            assert_eq!(sector.unit_vecs(3).unwrap().concat(), block(3));
+            assert!(matches!(sector.block(4), Err(PosError::InvalidParams(_))));
+
+            sector.write_units(0, &new_units).unwrap();
+            sector.flush().unwrap();
+            drop(sector);
+            assert_eq!(std::fs::read(path).unwrap()[data_offset(0)..data_offset(params.block_pl)], new_units.concat()[..]);
+            std::fs::write(path, &data).unwrap();
+        }
+
+        let mut sector = SectorAccess::open(path, &params, false).unwrap();
+        assert!(sector.write_units(0, &new_units).is_err());
+        std::fs::write(path, &data[..data.len() - 1]).unwrap();
+        assert!(matches!(SectorAccess::open(path, &params, false), Err(PosError::CorruptData(_))));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,6 +1,26 @@
+pub mod api;
+pub mod cache;
+pub mod checkpoint;
 pub mod depend;
 pub mod common;
+pub mod config;
+pub mod error;
 pub mod merkle_tree;
+pub mod mmap;
+pub mod params;
+#[cfg(feature = "experiments")]
 pub mod postorage;
+pub mod porep;
+pub mod post;
+pub mod proof;
+pub mod sector;
+pub mod stats;
+pub mod stream;
+pub mod progress;
 pub mod prover;
-pub mod verifier;
\ No newline at end of file
+pub mod repair;
+pub mod replica;
+pub mod scheduler;
+#[cfg(test)]
+mod test_util;
+pub mod verifier;
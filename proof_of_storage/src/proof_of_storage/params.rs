@@ -0,0 +1,274 @@
+use serde::{Serialize, Deserialize};
+
+use super::depend::{DependCount, LongDependency, ShortDependency};
+use super::error::{PosError, Result};
+
+use crate::vde::mode::VdeMode;
+use crate::vde::rug_sloth::{P_512, P_1024, P_2048};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PosPara {
+    // 扇区可容纳的原始数据长度，实际长度记录在扇区文件头部
+    pub data_l: usize,
+
+    pub unit_l: usize,
+    pub block_l: usize,
+    pub big_block_l: usize,
+
+    pub unit_pl: usize,
+    pub block_pl: usize,
+    pub big_block_pl: usize,
+
+    pub seal_rounds: usize,
+    pub vde_rounds: usize,
+    pub vde_mode: VdeMode,
+
+    // 二级数据块之间的长程依赖规则及依赖个数
+    pub long_depend: LongDependency,
+    // 二级数据块内部的短程依赖规则及依赖个数
+    pub short_depend: ShortDependency,
+
+    pub leaves_to_prove_count: usize,
+}
+
+/// 内置参数预设：(名称, 数据长度倍数, 每个二级数据块包含的一级数据块个数)
+pub const PRESETS: [(&str, usize, usize); 4] = [
+    ("1m", 1, 64),
+    ("4m", 4, 256),
+    ("16m", 16, 1024),
+    ("64m", 64, 4 * 1024),
+];
+
+pub fn preset(name: &str) -> Result<PosPara> {
+    //! 按名称获取内置参数，数据长度约为 1/4/16/64 MB
+    let &(_, l, unit_cnt) = PRESETS.iter().find(|(preset_name, _, _)| *preset_name == name)
+    .ok_or_else(|| PosError::InvalidParams(format!("unknown preset {:?}, expected one of {:?}", name, PRESETS.map(|(n, _, _)| n))))?;
+
+    PosPara::builder()
+    .data_l(63 * 16 * 1024 * l)
+    .unit_l(63)
+    .block_l(63 * unit_cnt)
+    .big_block_l(63 * 128 * 128)
+    .build()
+}
+
+pub fn prime_for_unit(unit_pl: usize) -> Result<&'static str> {
+    //! 根据 pad 后一级数据块的长度选择 vde 使用的素数
+    match unit_pl * 8 {
+        512 => Ok(P_512),
+        1024 => Ok(P_1024),
+        2048 => Ok(P_2048),
+        bits => Err(PosError::InvalidParams(format!("no prime for {}-bit units, supported sizes are 512, 1024 and 2048 bits", bits))),
+    }
+}
+
+impl PosPara {
+    pub fn builder() -> PosParaBuilder {
+        PosParaBuilder::default()
+    }
+
+    pub fn block_count(&self) -> usize {
+        //! 二级数据块个数，data_l 不是 block_l 的整数倍时最后一个二级数据块补 0
+        self.data_l.div_ceil(self.block_l)
+    }
+
+    pub fn id(&self) -> [u8; 32] {
+        //! 参数编号：参数 bincode 编码的 blake3 哈希值，证明中携带该编号以确认双方使用同一组参数
+        let encoded = bincode::serialize(self).expect("PosPara always serializes");
+        *blake3::hash(&encoded).as_bytes()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        //! 检查参数之间是否一致，封装、解封装前调用
+        if self.unit_l == 0 || self.block_l == 0 || self.big_block_l == 0 || self.data_l == 0 {
+            return Err(PosError::InvalidParams("data_l, unit_l, block_l and big_block_l must be greater than 0".to_string()));
+        }
+        if self.block_l % self.unit_l != 0 {
+            return Err(PosError::InvalidParams(format!("block_l ({}) is not a multiple of unit_l ({})", self.block_l, self.unit_l)));
+        }
+        if self.big_block_l % self.block_l != 0 {
+            return Err(PosError::InvalidParams(format!("big_block_l ({}) is not a multiple of block_l ({})", self.big_block_l, self.block_l)));
+        }
+
+        let unit_pl = self.unit_l + 1;
+        if (self.unit_pl, self.block_pl, self.big_block_pl) != (unit_pl, self.block_l / self.unit_l * unit_pl, self.big_block_l / self.unit_l * unit_pl) {
+            return Err(PosError::InvalidParams(format!("padded lengths ({}, {}, {}) do not match unit_l ({})", self.unit_pl, self.block_pl, self.big_block_pl, self.unit_l)));
+        }
+        prime_for_unit(self.unit_pl)?;
+        if !self.vde_mode.vde().unit_bits().contains(&(self.unit_pl * 8)) {
+            return Err(PosError::InvalidParams(format!("vde mode {} does not support {}-bit units", self.vde_mode, self.unit_pl * 8)));
+        }
+
+        if self.seal_rounds == 0 || self.vde_rounds == 0 {
+            return Err(PosError::InvalidParams("seal_rounds and vde_rounds must be greater than 0".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// 由原始长度构造 PosPara，pad 后的长度由 build 推导
+#[derive(Clone, Debug)]
+pub struct PosParaBuilder {
+    data_l: usize,
+    unit_l: usize,
+    block_l: usize,
+    big_block_l: usize,
+    seal_rounds: usize,
+    vde_rounds: usize,
+    vde_mode: VdeMode,
+    long_depend: LongDependency,
+    short_depend: ShortDependency,
+    leaves_to_prove_count: usize,
+}
+
+impl Default for PosParaBuilder {
+    fn default() -> Self {
+        PosParaBuilder {
+            data_l: 63 * 16 * 1024,
+            unit_l: 63,
+            block_l: 63 * 64,
+            big_block_l: 63 * 128 * 128,
+            seal_rounds: 2,
+            vde_rounds: 10,
+            vde_mode: VdeMode::Sloth,
+            long_depend: LongDependency::Random(DependCount::Growing),
+            short_depend: ShortDependency::Random(10),
+            leaves_to_prove_count: 3,
+        }
+    }
+}
+
+impl From<&PosPara> for PosParaBuilder {
+    fn from(params: &PosPara) -> Self {
+        PosParaBuilder {
+            data_l: params.data_l,
+            unit_l: params.unit_l,
+            block_l: params.block_l,
+            big_block_l: params.big_block_l,
+            seal_rounds: params.seal_rounds,
+            vde_rounds: params.vde_rounds,
+            vde_mode: params.vde_mode,
+            long_depend: params.long_depend,
+            short_depend: params.short_depend,
+            leaves_to_prove_count: params.leaves_to_prove_count,
+        }
+    }
+}
+
+impl PosParaBuilder {
+    pub fn data_l(mut self, data_l: usize) -> Self {
+        self.data_l = data_l;
+        self
+    }
+
+    pub fn unit_l(mut self, unit_l: usize) -> Self {
+        self.unit_l = unit_l;
+        self
+    }
+
+    pub fn block_l(mut self, block_l: usize) -> Self {
+        self.block_l = block_l;
+        self
+    }
+
+    pub fn big_block_l(mut self, big_block_l: usize) -> Self {
+        self.big_block_l = big_block_l;
+        self
+    }
+
+    pub fn seal_rounds(mut self, seal_rounds: usize) -> Self {
+        self.seal_rounds = seal_rounds;
+        self
+    }
+
+    pub fn vde_rounds(mut self, vde_rounds: usize) -> Self {
+        self.vde_rounds = vde_rounds;
+        self
+    }
+
+    pub fn vde_mode(mut self, vde_mode: VdeMode) -> Self {
+        self.vde_mode = vde_mode;
+        self
+    }
+
+    pub fn long_depend(mut self, long_depend: LongDependency) -> Self {
+        self.long_depend = long_depend;
+        self
+    }
+
+    pub fn short_depend(mut self, short_depend: ShortDependency) -> Self {
+        self.short_depend = short_depend;
+        self
+    }
+
+    pub fn leaves_to_prove_count(mut self, count: usize) -> Self {
+        self.leaves_to_prove_count = count;
+        self
+    }
+
+    pub fn build(self) -> Result<PosPara> {
+        if self.unit_l == 0 {
+            return Err(PosError::InvalidParams("unit_l must be greater than 0".to_string()));
+        }
+        // 每个一级数据块在高位 pad 一个字节
+        let unit_pl = self.unit_l + 1;
+        let params = PosPara {
+            data_l: self.data_l,
+            unit_l: self.unit_l,
+            block_l: self.block_l,
+            big_block_l: self.big_block_l,
+            unit_pl,
+            block_pl: self.block_l / self.unit_l * unit_pl,
+            big_block_pl: self.big_block_l / self.unit_l * unit_pl,
+            seal_rounds: self.seal_rounds,
+            vde_rounds: self.vde_rounds,
+            vde_mode: self.vde_mode,
+            long_depend: self.long_depend,
+            short_depend: self.short_depend,
+            leaves_to_prove_count: self.leaves_to_prove_count,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let params = PosPara::builder().data_l(63 * 4 * 8).block_l(63 * 4).big_block_l(63 * 4 * 8).build().unwrap();
+        assert_eq!((params.unit_pl, params.block_pl, params.big_block_pl), (64, 64 * 4, 64 * 4 * 8));
+
+        for (name, l, _) in PRESETS {
+            assert_eq!(preset(name).unwrap().data_l, 63 * 16 * 1024 * l);
+        }
+        assert!(matches!(preset("2m"), Err(PosError::InvalidParams(_))));
+
+        // data_l 可以不是 block_l 的整数倍
+        let params = PosPara::builder().data_l(63 * 64 * 3 + 63).build().unwrap();
+        assert_eq!(params.block_count(), 4);
+        assert_eq!(preset("1m").unwrap().block_count(), 16 * 1024 / 64);
+
+        assert_eq!(preset("1m").unwrap().id(), preset("1m").unwrap().id());
+        assert_ne!(preset("1m").unwrap().id(), preset("4m").unwrap().id());
+    }
+
+    #[test]
+    fn test_builder_rejects() {
+        let bad = [
+            PosPara::builder().block_l(63 * 64 + 1),
+            PosPara::builder().data_l(0),
+            PosPara::builder().unit_l(62).block_l(62 * 64).big_block_l(62 * 64).data_l(62 * 64),
+            PosPara::builder().seal_rounds(0),
+        ];
+        for builder in bad {
+            assert!(matches!(builder.build(), Err(PosError::InvalidParams(_))));
+        }
+
+        let mut params = PosPara::builder().build().unwrap();
+        params.block_pl += 1;
+        assert!(params.validate().is_err());
+    }
+}
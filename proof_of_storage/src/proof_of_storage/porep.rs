@@ -0,0 +1,251 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::OpenOptions;
+
+use serde::{Serialize, Deserialize};
+
+use super::api::{read_comm_r_tree, read_opened_block};
+use super::common::{to_units, com_units, blake3_hash};
+use super::error::{PosError, Result};
+use super::merkle_tree::{generate_merkle_tree_from_data, generate_merkle_proof, verify_merkle_proof_bytes};
+use super::params::PosPara;
+use super::proof::StorageProof;
+use super::progress::Progress;
+use super::prover::prepare_params;
+use super::replica::ReplicaId;
+use super::sector::{SectorHeader, read_header, read_origin_leaves};
+use super::verifier::{create_challenges, derive_challenges, batch_unseal};
+
+/// 复制证明的挑战：被挑战的二级数据块编号（升序且互不相同）及其所属副本
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PorepChallenge {
+    pub replica_id: ReplicaId,
+    pub comm_r: [u8; 32],
+    pub block_cnt: usize,
+    pub indices: Vec<usize>,
+}
+
+fn check_indices(indices: &Vec<usize>, block_cnt: usize) -> Result<()> {
+    //! 挑战编号必须非空、升序、互不相同且不越界
+    if indices.is_empty() || indices.windows(2).any(|w| w[0] >= w[1]) || indices[indices.len() - 1] >= block_cnt {
+        return Err(PosError::InvalidParams(format!("challenged blocks {:?} must be sorted, distinct and below {}", indices, block_cnt)));
+    }
+    Ok(())
+}
+
+fn opened_blocks(params: &PosPara, indices: &Vec<usize>, blocks_id: &Vec<Vec<u8>>) -> Vec<usize> {
+    //! 解封装被挑战的二级数据块需要打开的全部二级数据块编号
+    let block_cnt = params.block_count();
+    let mut res = BTreeSet::new();
+    for &idx2 in indices {
+        res.insert(idx2);
+        if idx2 > 0 {
+            res.insert(idx2 - 1);
+            res.extend(params.long_depend.indices(block_cnt, idx2, &blocks_id[idx2 - 1]));
+        }
+    }
+    res.into_iter().collect()
+}
+
+impl PorepChallenge {
+    pub fn from_seed(seed: &[u8; 32], replica_id: ReplicaId, comm_r: [u8; 32], block_cnt: usize, count: usize) -> Result<PorepChallenge> {
+        //! 由公开种子导出挑战（Fiat–Shamir），证明者无需与验证者交互即可生成证明
+        if count == 0 || count > block_cnt {
+            return Err(PosError::InvalidParams(format!("challenge count {} must be in 1..={}", count, block_cnt)));
+        }
+        Ok(PorepChallenge {
+            replica_id,
+            comm_r,
+            block_cnt,
+            indices: derive_challenges(seed, &comm_r, &replica_id.id(), count, (0, block_cnt))?,
+        })
+    }
+}
+
+impl StorageProof {
+    pub fn generate_replica(sealed: &str, challenge: &PorepChallenge) -> Result<StorageProof> {
+        //! 证明者只由扇区文件应答复制证明的挑战：打开被挑战的二级数据块、其前一个二级数据块及其长程依赖，
+        //! comm_r、comm_d 的路径由扇区文件末尾保存的叶子生成，不需要原始文件，也不必读取其余的二级数据块
+        let mut file = OpenOptions::new()
+        .read(true)
+        .open(sealed)?;
+        let header = read_header(&mut file)?;
+        header.check_sealed()?;
+
+        let params = &header.params;
+        let block_cnt = params.block_count();
+        if challenge.replica_id != header.replica_id() || challenge.comm_r != header.comm_r || challenge.block_cnt != block_cnt {
+            return Err(PosError::InvalidParams("challenge was issued for another replica".to_string()));
+        }
+        check_indices(&challenge.indices, block_cnt)?;
+
+        let (blocks_id, comm_r_tree) = read_comm_r_tree(&mut file, &header)?;
+        let (_, comm_d_tree, comm_d) = generate_merkle_tree_from_data(&read_origin_leaves(&mut file, params)?)?;
+        if comm_d != header.comm_d {
+            return Err(PosError::CorruptData("stored leaves do not match comm_d in the header".to_string()));
+        }
+
+        let opened_indices = opened_blocks(params, &challenge.indices, &blocks_id);
+        let depend_indices: Vec<usize> = opened_indices.iter().copied().filter(|idx2| challenge.indices.binary_search(idx2).is_err()).collect();
+        let mut read_blocks = |indices: &Vec<usize>| -> Result<Vec<Vec<u8>>> {
+            let mut res = vec![];
+            for &idx2 in indices {
+                res.push(read_opened_block(&mut file, params, idx2, &blocks_id)?);
+            }
+            Ok(res)
+        };
+
+        Ok(StorageProof {
+            params_id: params.id(),
+            indices: challenge.indices.clone(),
+            blocks: read_blocks(&challenge.indices)?,
+            depend_blocks: read_blocks(&depend_indices)?,
+            depend_indices,
+            comm_r_proof: generate_merkle_proof(&opened_indices, &comm_r_tree)?.to_bytes(),
+            comm_d_proof: Some(generate_merkle_proof(&challenge.indices, &comm_d_tree)?.to_bytes()),
+        })
+    }
+}
+
+/// 复制证明的验证者：只需保存参数、副本编号（含 comm_d）与 comm_r，在内存中解封装被挑战的二级数据块
+pub struct PorepVerifier {
+    params: PosPara,
+    replica_id: ReplicaId,
+    comm_r: [u8; 32],
+}
+
+impl PorepVerifier {
+    pub fn new(params: PosPara, replica_id: ReplicaId, comm_r: [u8; 32]) -> Result<PorepVerifier> {
+        params.validate()?;
+        Ok(PorepVerifier { params, replica_id, comm_r })
+    }
+
+    pub fn from_header(header: &SectorHeader) -> Result<PorepVerifier> {
+        PorepVerifier::new(header.params.clone(), header.replica_id(), header.comm_r)
+    }
+
+    pub fn challenge(&self, count: usize) -> Result<PorepChallenge> {
+        //! 随机挑战 count 个不同的二级数据块
+        let block_cnt = self.params.block_count();
+        if count == 0 || count > block_cnt {
+            return Err(PosError::InvalidParams(format!("challenge count {} must be in 1..={}", count, block_cnt)));
+        }
+        Ok(PorepChallenge {
+            replica_id: self.replica_id,
+            comm_r: self.comm_r,
+            block_cnt,
+            indices: create_challenges(count, (0, block_cnt))?,
+        })
+    }
+
+    pub fn challenge_from_seed(&self, seed: &[u8; 32], count: usize) -> Result<PorepChallenge> {
+        PorepChallenge::from_seed(seed, self.replica_id, self.comm_r, self.params.block_count(), count)
+    }
+
+    pub fn verify(&self, challenge: &PorepChallenge, proof: &StorageProof) -> Result<()> {
+        //! 1. 打开的封装后二级数据块属于 comm_r；
+        //! 2. 由前一个二级数据块重新计算长程依赖，在内存中解封装被挑战的二级数据块；
+        //! 3. 解封装结果的哈希值是 comm_d 中对应的叶子
+        let params = &self.params;
+        let block_cnt = params.block_count();
+        if challenge.replica_id != self.replica_id || challenge.comm_r != self.comm_r || challenge.block_cnt != block_cnt {
+            return Err(PosError::InvalidProof("challenge was issued for another replica".to_string()));
+        }
+        check_indices(&challenge.indices, block_cnt).map_err(|err| PosError::InvalidProof(err.to_string()))?;
+        proof.check_shape(&params.id(), &challenge.indices)?;
+        let comm_d_proof = proof.comm_d_proof.as_ref().ok_or_else(|| PosError::InvalidProof("proof has no comm_d path".to_string()))?;
+        let opened: BTreeMap<usize, &Vec<u8>> = proof.opened().into_iter().collect();
+        if opened.values().any(|block| block.len() != params.block_pl) {
+            return Err(PosError::InvalidProof(format!("opened blocks must be {} bytes", params.block_pl)));
+        }
+        proof.verify_comm_r(self.comm_r, block_cnt)?;
+
+        let get_block = |idx2: usize| opened.get(&idx2).copied().ok_or_else(|| PosError::InvalidProof(format!("proof does not open block {}", idx2)));
+
+        let mut blocks = vec![];
+        let mut before_block_ids = vec![];
+        let mut depend_blocks = vec![];
+        for &idx2 in &challenge.indices {
+            blocks.push(to_units(get_block(idx2)?, params.unit_pl));
+            let mut depend = vec![];
+            if idx2 == 0 {
+                before_block_ids.push(vec![]);
+            }
+            else {
+                let before_block_id = blake3_hash(get_block(idx2 - 1)?);
+                for i in params.long_depend.indices(block_cnt, idx2, &before_block_id) {
+                    depend.push(to_units(get_block(i)?, params.unit_pl));
+                }
+                before_block_ids.push(before_block_id);
+            }
+            depend_blocks.push(depend);
+        }
+
+        let vde_key = prepare_params(params.unit_pl)?;
+        let unsealed_blocks = batch_unseal(params, &challenge.indices, &mut blocks, &before_block_ids, &depend_blocks, &vde_key, &self.replica_id.iv(), &Progress::none())?;
+        let origin_leaves = unsealed_blocks.iter().map(|block| blake3_hash(&com_units(block))).collect();
+        verify_merkle_proof_bytes(comm_d_proof, self.replica_id.comm_d, &challenge.indices, &origin_leaves, block_cnt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use crate::proof_of_storage::sector::leaves_offset;
+    use crate::proof_of_storage::test_util::{test_params, temp_path, seal_sector};
+
+    #[test]
+    fn test_porep() {
+        let params = test_params();
+        let sealed = temp_path("pos_test_porep_sealed");
+        let data: Vec<u8> = (0..1800).map(|i| (i * 13 % 251) as u8).collect();
+        // 验证者只需要文件头部，不需要原始数据
+        let header = seal_sector(&params, &data, 3, &sealed);
+
+        let verifier = PorepVerifier::from_header(&header).unwrap();
+        let challenge = PorepChallenge { indices: vec![0, 4, 7], ..verifier.challenge(3).unwrap() };
+        let proof = StorageProof::generate_replica(&sealed, &challenge).unwrap();
+        assert!(proof.depend_indices.contains(&3) && proof.depend_indices.contains(&6));
+        verifier.verify(&challenge, &proof).unwrap();
+
+        let random = verifier.challenge(5).unwrap();
+        verifier.verify(&random, &StorageProof::generate_replica(&sealed, &random).unwrap()).unwrap();
+
+        // 非交互：证明者由公开种子导出挑战，任何人重新导出挑战后验证
+        let seeded = PorepChallenge::from_seed(&[9u8; 32], header.replica_id(), header.comm_r, 8, 4).unwrap();
+        let seeded_proof = StorageProof::generate_replica(&sealed, &seeded).unwrap();
+        verifier.verify(&verifier.challenge_from_seed(&[9u8; 32], 4).unwrap(), &seeded_proof).unwrap();
+        assert!(verifier.verify(&verifier.challenge_from_seed(&[8u8; 32], 4).unwrap(), &seeded_proof).is_err());
+
+        // 篡改被打开的二级数据块、少打开依赖块或换用另一个副本编号时验证失败
+        let mut bad = proof.clone();
+        bad.blocks[1][0] ^= 1;
+        assert!(matches!(verifier.verify(&challenge, &bad), Err(PosError::InvalidProof(_))));
+        let mut bad = proof.clone();
+        bad.depend_blocks[0][0] ^= 1;
+        assert!(matches!(verifier.verify(&challenge, &bad), Err(PosError::InvalidProof(_))));
+        let decoded = StorageProof::from_bytes(&proof.to_bytes().unwrap()).unwrap();
+        verifier.verify(&challenge, &decoded).unwrap();
+        // 只应答存储挑战的证明不能作为复制证明
+        assert!(verifier.verify(&challenge, &StorageProof { comm_d_proof: None, ..proof.clone() }).is_err());
+        let mut bad = proof.clone();
+        let pos = bad.depend_indices.iter().position(|&i| i == 3).unwrap();
+        bad.depend_indices.remove(pos);
+        bad.depend_blocks.remove(pos);
+        assert!(matches!(verifier.verify(&challenge, &bad), Err(PosError::InvalidProof(_))));
+
+        let other = ReplicaId { ticket: [3u8; 32], ..header.replica_id() };
+        let other_verifier = PorepVerifier::new(params.clone(), other, header.comm_r).unwrap();
+        let other_challenge = PorepChallenge { replica_id: other, ..challenge.clone() };
+        assert!(StorageProof::generate_replica(&sealed, &other_challenge).is_err());
+        assert!(matches!(other_verifier.verify(&other_challenge, &proof), Err(PosError::InvalidProof(_))));
+
+        // 扇区文件末尾的 comm_d 叶子损坏时证明者拒绝生成证明
+        let mut content = fs::read(&sealed).unwrap();
+        content[leaves_offset(&params)] ^= 1;
+        fs::write(&sealed, &content).unwrap();
+        assert!(matches!(StorageProof::generate_replica(&sealed, &challenge), Err(PosError::CorruptData(_))));
+
+        fs::remove_file(sealed).unwrap();
+    }
+}
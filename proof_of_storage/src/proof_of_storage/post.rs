@@ -0,0 +1,411 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Deserialize};
+
+use super::api::{Challenge, Verifier};
+use super::error::{PosError, Result};
+use super::proof::StorageProof;
+use super::replica::ReplicaId;
+use super::sector::SectorHeader;
+
+/// 由信标随机数导出各窗口种子时使用的域分隔前缀
+const WINDOW_SEED_TAG: &[u8] = b"postorage/post-window/v2";
+
+/// 时空证明使用的时钟，以 epoch 计时
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+/// 按系统时间计算 epoch，每个 epoch 持续 epoch_secs 秒
+pub struct SystemClock {
+    epoch_secs: u64,
+}
+
+impl SystemClock {
+    pub fn new(epoch_secs: u64) -> Result<SystemClock> {
+        if epoch_secs == 0 {
+            return Err(PosError::InvalidParams("epoch_secs must be greater than 0".to_string()));
+        }
+        Ok(SystemClock { epoch_secs })
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0) / self.epoch_secs
+    }
+}
+
+/// 外部随机信标，如区块链在某个 epoch 的区块哈希或公开的随机数信标，到达该 epoch 之前无法预知其取值
+pub trait Beacon {
+    fn randomness(&self, epoch: u64) -> Result<[u8; 32]>;
+}
+
+impl<F: Fn(u64) -> Result<[u8; 32]>> Beacon for F {
+    fn randomness(&self, epoch: u64) -> Result<[u8; 32]> {
+        self(epoch)
+    }
+}
+
+/// 模拟时钟，只在调用 advance / set 时前进，克隆出的时钟共享同一个时间
+#[derive(Clone, Default)]
+pub struct SimulatedClock {
+    epoch: Arc<AtomicU64>,
+}
+
+impl SimulatedClock {
+    pub fn new(epoch: u64) -> SimulatedClock {
+        SimulatedClock { epoch: Arc::new(AtomicU64::new(epoch)) }
+    }
+
+    pub fn advance(&self, epochs: u64) {
+        self.epoch.fetch_add(epochs, Ordering::SeqCst);
+    }
+
+    pub fn set(&self, epoch: u64) {
+        self.epoch.store(epoch, Ordering::SeqCst);
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> u64 {
+        self.epoch.load(Ordering::SeqCst)
+    }
+}
+
+/// 时空证明参数
+///
+/// deadlines: 扇区按加入顺序轮流分配到的截止期个数，第 w 个窗口挑战截止期 w % deadlines 中的扇区
+///
+/// window_epochs: 每个窗口持续的 epoch 数，证明必须在窗口关闭前提交
+///
+/// challenge_count: 每个扇区每个窗口挑战的二级数据块个数，超过扇区的二级数据块个数时挑战全部二级数据块
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PostConfig {
+    pub deadlines: usize,
+    pub window_epochs: u64,
+    pub challenge_count: usize,
+}
+
+impl PostConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.deadlines == 0 || self.window_epochs == 0 || self.challenge_count == 0 {
+            return Err(PosError::InvalidParams(format!("deadlines, window_epochs and challenge_count must be greater than 0, got {:?}", self)));
+        }
+        Ok(())
+    }
+}
+
+/// 参与时空证明的扇区，验证者只需要扇区文件头部中的公开信息
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PostSector {
+    pub replica_id: ReplicaId,
+    pub params_id: [u8; 32],
+    pub comm_r: [u8; 32],
+    pub block_cnt: usize,
+}
+
+impl PostSector {
+    pub fn from_header(header: &SectorHeader) -> PostSector {
+        PostSector {
+            replica_id: header.replica_id(),
+            params_id: header.params.id(),
+            comm_r: header.comm_r,
+            block_cnt: header.params.block_count(),
+        }
+    }
+
+    pub fn sector_num(&self) -> u64 {
+        self.replica_id.sector_num
+    }
+
+    fn verifier(&self) -> Verifier {
+        Verifier::new(self.params_id, self.comm_r, self.block_cnt).with_replica_id(self.replica_id.id())
+    }
+}
+
+/// 一个挑战窗口：[open_epoch, close_epoch) 内需要对截止期 deadline 中的每个扇区应答 challenges
+///
+/// beacon: 窗口开放时（open_epoch）外部随机信标的取值，seed 由它导出
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PostWindow {
+    pub window: u64,
+    pub deadline: usize,
+    pub open_epoch: u64,
+    pub close_epoch: u64,
+    pub beacon: [u8; 32],
+    pub seed: [u8; 32],
+    // 扇区编号 -> 挑战
+    pub challenges: BTreeMap<u64, Challenge>,
+}
+
+impl PostWindow {
+    pub fn prove(&self, sector_num: u64, sealed: &str) -> Result<StorageProof> {
+        //! 证明者由扇区文件应答本窗口中扇区 sector_num 的挑战
+        let challenge = self.challenges.get(&sector_num).ok_or_else(|| PosError::InvalidParams(format!("sector {} is not challenged in window {}", sector_num, self.window)))?;
+        StorageProof::generate(sealed, challenge)
+    }
+}
+
+/// 扇区在一个窗口中的结果
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum WindowStatus {
+    Proven,
+    // 提交的证明未通过验证
+    Faulty(String),
+    // 窗口关闭前没有提交证明
+    Missed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WindowRecord {
+    pub window: u64,
+    pub deadline: usize,
+    pub sector_num: u64,
+    pub status: WindowStatus,
+}
+
+/// 时空证明调度器：将扇区划分到各截止期，由每个窗口开放时的信标随机数导出挑战，收集证明者的应答并记录结果
+///
+/// 信标在窗口开放前无法预知，证明者不能提前计算之后窗口的挑战；信标须由调用者自行取得，不能采用证明者给出的值
+pub struct PostScheduler<C: Clock> {
+    config: PostConfig,
+    clock: C,
+    start_epoch: u64,
+    // 扇区编号 -> (截止期, 扇区)
+    sectors: BTreeMap<u64, (usize, PostSector)>,
+    // 已经提交过证明的窗口
+    submitted: BTreeSet<u64>,
+    // 第一个尚未结算的窗口
+    next_window: u64,
+    records: Vec<WindowRecord>,
+}
+
+impl<C: Clock> PostScheduler<C> {
+    pub fn new(config: PostConfig, clock: C) -> Result<PostScheduler<C>> {
+        //! 第 0 个窗口从创建时的 epoch 开始
+        config.validate()?;
+        let start_epoch = clock.now();
+        Ok(PostScheduler {
+            config,
+            clock,
+            start_epoch,
+            sectors: BTreeMap::new(),
+            submitted: BTreeSet::new(),
+            next_window: 0,
+            records: vec![],
+        })
+    }
+
+    pub fn config(&self) -> &PostConfig {
+        &self.config
+    }
+
+    pub fn add_sector(&mut self, sector: PostSector) -> Result<usize> {
+        //! 将扇区轮流分配到各截止期，返回分配到的截止期
+        let sector_num = sector.sector_num();
+        if self.sectors.contains_key(&sector_num) {
+            return Err(PosError::InvalidParams(format!("sector {} is already scheduled", sector_num)));
+        }
+        let deadline = self.sectors.len() % self.config.deadlines;
+        self.sectors.insert(sector_num, (deadline, sector));
+        Ok(deadline)
+    }
+
+    pub fn deadline_of(&self, sector_num: u64) -> Option<usize> {
+        self.sectors.get(&sector_num).map(|&(deadline, _)| deadline)
+    }
+
+    pub fn window_seed(window: u64, beacon: &[u8; 32]) -> [u8; 32] {
+        //! blake3(tag || beacon || window)，任何知道该窗口信标的人都能重新计算
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(WINDOW_SEED_TAG);
+        hasher.update(beacon);
+        hasher.update(&window.to_le_bytes());
+        *hasher.finalize().as_bytes()
+    }
+
+    pub fn open_epoch(&self, window: u64) -> u64 {
+        //! 第 window 个窗口开放的 epoch，调用者取该 epoch 的信标
+        self.start_epoch + window * self.config.window_epochs
+    }
+
+    pub fn current_window<B: Beacon>(&mut self, beacon: &B) -> Result<PostWindow> {
+        //! 当前开放的窗口及其挑战，挑战由该窗口 open_epoch 的信标导出，同时结算已经关闭的窗口
+        self.poll();
+        let window = self.window_at(self.clock.now());
+        self.window(window, &beacon.randomness(self.open_epoch(window))?)
+    }
+
+    pub fn window(&self, window: u64, beacon: &[u8; 32]) -> Result<PostWindow> {
+        //! 第 window 个窗口的挑战，挑战由该窗口 open_epoch 的信标 beacon 与扇区决定
+        let deadline = (window % self.config.deadlines as u64) as usize;
+        let seed = Self::window_seed(window, beacon);
+        let mut challenges = BTreeMap::new();
+        for (&sector_num, (_, sector)) in self.sectors.iter().filter(|(_, (d, _))| *d == deadline) {
+            let count = usize::min(self.config.challenge_count, sector.block_cnt);
+            challenges.insert(sector_num, sector.verifier().challenge_from_seed(&seed, count)?);
+        }
+
+        let open_epoch = self.open_epoch(window);
+        Ok(PostWindow {
+            window,
+            deadline,
+            open_epoch,
+            close_epoch: open_epoch + self.config.window_epochs,
+            beacon: *beacon,
+            seed,
+            challenges,
+        })
+    }
+
+    pub fn submit<B: Beacon>(&mut self, window: u64, beacon: &B, proofs: &BTreeMap<u64, StorageProof>) -> Result<Vec<WindowRecord>> {
+        //! 提交第 window 个窗口的证明，只接受当前开放的窗口，每个窗口只能提交一次
+        //!
+        //! 由该窗口 open_epoch 的信标重新导出挑战，beacon 应与 current_window 使用的相同
+        //!
+        //! 逐个扇区验证并记录结果：没有证明的扇区记为 Missed，未通过验证的记为 Faulty
+        self.poll();
+        let current = self.window_at(self.clock.now());
+        if window != current {
+            return Err(PosError::InvalidParams(format!("window {} is not open, current window is {}", window, current)));
+        }
+        if !self.submitted.insert(window) {
+            return Err(PosError::InvalidParams(format!("proofs for window {} were already submitted", window)));
+        }
+
+        let post_window = self.window(window, &beacon.randomness(self.open_epoch(window))?)?;
+        let mut res = vec![];
+        for (&sector_num, challenge) in &post_window.challenges {
+            let status = match proofs.get(&sector_num) {
+                None => WindowStatus::Missed,
+                Some(proof) => match self.sectors[&sector_num].1.verifier().verify(challenge, proof) {
+                    Ok(()) => WindowStatus::Proven,
+                    Err(err) => WindowStatus::Faulty(err.to_string()),
+                },
+            };
+            res.push(WindowRecord { window, deadline: post_window.deadline, sector_num, status });
+        }
+        self.records.extend(res.iter().cloned());
+        Ok(res)
+    }
+
+    pub fn poll(&mut self) {
+        //! 结算所有已经关闭的窗口：没有提交证明的窗口中各扇区记为 Missed
+        let current = self.window_at(self.clock.now());
+        while self.next_window < current {
+            let window = self.next_window;
+            if !self.submitted.contains(&window) {
+                let deadline = (window % self.config.deadlines as u64) as usize;
+                for (&sector_num, _) in self.sectors.iter().filter(|(_, (d, _))| *d == deadline) {
+                    self.records.push(WindowRecord { window, deadline, sector_num, status: WindowStatus::Missed });
+                }
+            }
+            self.submitted.remove(&window);
+            self.next_window += 1;
+        }
+    }
+
+    pub fn records(&self) -> &Vec<WindowRecord> {
+        &self.records
+    }
+
+    pub fn faults(&self) -> Vec<&WindowRecord> {
+        //! 所有 Missed 或 Faulty 的记录
+        self.records.iter().filter(|record| record.status != WindowStatus::Proven).collect()
+    }
+
+    fn window_at(&self, epoch: u64) -> u64 {
+        epoch.saturating_sub(self.start_epoch) / self.config.window_epochs
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use crate::proof_of_storage::test_util::{test_params_with_blocks, temp_path, seal_sector};
+
+    #[test]
+    fn test_post_scheduler() {
+        let params = test_params_with_blocks(4);
+
+        let clock = SimulatedClock::new(100);
+        let config = PostConfig { deadlines: 2, window_epochs: 10, challenge_count: 2 };
+        let mut scheduler = PostScheduler::new(config, clock.clone()).unwrap();
+        let beacon = |epoch: u64| -> Result<[u8; 32]> { Ok(*blake3::hash(&epoch.to_le_bytes()).as_bytes()) };
+        let mut sealed = BTreeMap::new();
+        for sector_num in 0..3 {
+            let path = temp_path(&format!("pos_test_post_sealed_{}", sector_num));
+            let header = seal_sector(&params, &[7u8; 900], sector_num, &path);
+            scheduler.add_sector(PostSector::from_header(&header)).unwrap();
+            sealed.insert(sector_num, path);
+        }
+        assert_eq!([0, 1, 2].map(|i| scheduler.deadline_of(i).unwrap()), [0, 1, 0]);
+        assert!(scheduler.add_sector(PostSector::from_header(&crate::read_header_from(&sealed[&0]).unwrap())).is_err());
+
+        let prove_all = |window: &PostWindow| -> BTreeMap<u64, StorageProof> {
+            window.challenges.keys().map(|&i| (i, window.prove(i, &sealed[&i]).unwrap())).collect()
+        };
+
+        // 窗口 0：截止期 0 中的扇区 0、2 按时提交
+        let window = scheduler.current_window(&beacon).unwrap();
+        assert_eq!((window.window, window.deadline, window.open_epoch, window.close_epoch), (0, 0, 100, 110));
+        assert_eq!(window.challenges.keys().copied().collect::<Vec<u64>>(), vec![0, 2]);
+        assert_eq!(window, scheduler.window(0, &beacon(100).unwrap()).unwrap());
+        let res = scheduler.submit(0, &beacon, &prove_all(&window)).unwrap();
+        assert!(res.iter().all(|record| record.status == WindowStatus::Proven));
+        assert!(scheduler.submit(0, &beacon, &prove_all(&window)).is_err());
+
+        // 窗口 1 没有提交，窗口 2 中扇区 2 的证明被篡改，窗口 3 中扇区 1 按时提交
+        clock.advance(25);
+        let window = scheduler.current_window(&beacon).unwrap();
+        assert_eq!(window.window, 2);
+        let mut proofs = prove_all(&window);
+        proofs.get_mut(&2).unwrap().blocks[0][0] ^= 1;
+        scheduler.submit(2, &beacon, &proofs).unwrap();
+        clock.advance(10);
+        let window = scheduler.current_window(&beacon).unwrap();
+        assert!(scheduler.submit(2, &beacon, &BTreeMap::new()).is_err());
+        scheduler.submit(window.window, &beacon, &prove_all(&window)).unwrap();
+
+        // 窗口 4 完全错过
+        clock.set(150);
+        scheduler.poll();
+        let faults: Vec<(u64, u64, bool)> = scheduler.faults().iter().map(|r| (r.window, r.sector_num, r.status == WindowStatus::Missed)).collect();
+        assert_eq!(faults, vec![(1, 1, true), (2, 2, false), (4, 0, true), (4, 2, true)]);
+        assert_eq!(scheduler.records().len(), 8);
+
+        for (_, path) in sealed {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_window_beacon() {
+        let clock = SimulatedClock::new(0);
+        let config = PostConfig { deadlines: 1, window_epochs: 10, challenge_count: 16 };
+        let mut scheduler = PostScheduler::new(config, clock.clone()).unwrap();
+        let replica_id = ReplicaId::new([1u8; 32], 0, [2u8; 32], [3u8; 32]);
+        scheduler.add_sector(PostSector { replica_id, params_id: [4u8; 32], comm_r: [5u8; 32], block_cnt: 1024 }).unwrap();
+
+        // 同一个窗口的信标不同，挑战互不相关；信标相同时任何人都能重新导出同样的挑战
+        let (a, b) = (scheduler.window(1, &[6u8; 32]).unwrap(), scheduler.window(1, &[7u8; 32]).unwrap());
+        assert_ne!(a.seed, b.seed);
+        let (a, b) = (&a.challenges[&0].indices, &b.challenges[&0].indices);
+        assert!(a.iter().filter(|i| b.contains(i)).count() < 4);
+        assert_eq!(&scheduler.window(1, &[6u8; 32]).unwrap().challenges[&0].indices, a);
+        // 同一个信标用于不同的窗口时种子也不同
+        assert_ne!(scheduler.window(2, &[6u8; 32]).unwrap().seed, scheduler.window(1, &[6u8; 32]).unwrap().seed);
+
+        // current_window 取窗口开放时的信标，信标尚不可用时不发出挑战
+        clock.set(25);
+        let window = scheduler.current_window(&|epoch: u64| -> Result<[u8; 32]> { Ok([epoch as u8; 32]) }).unwrap();
+        assert_eq!((window.window, window.open_epoch, window.beacon), (2, 20, [20u8; 32]));
+        let unavailable = |_: u64| -> Result<[u8; 32]> { Err(PosError::InvalidParams("beacon is not available yet".to_string())) };
+        assert!(scheduler.current_window(&unavailable).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use super::error::{PosError, Result};
+
+/// 进度事件所属的阶段
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    // 将原始数据 pad 后写入扇区文件
+    Pad,
+    Seal,
+    Unseal,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProgressEvent {
+    PhaseStarted { phase: Phase, total: usize },
+    // 第 idx2 个二级数据块处理完成，本阶段共完成 done / total 个
+    BlockDone { phase: Phase, idx2: usize, done: usize, total: usize },
+    // 本阶段又读写了 bytes 字节的原始文件或扇区文件中的二级数据块，供限速等观察者使用
+    Io { phase: Phase, bytes: u64 },
+    PhaseFinished { phase: Phase, elapsed: Duration },
+}
+
+/// 进度观察者，可能在解封装的工作线程中被调用
+pub trait ProgressObserver: Send + Sync {
+    fn on_event(&self, event: &ProgressEvent);
+}
+
+impl<F: Fn(&ProgressEvent) + Send + Sync> ProgressObserver for F {
+    fn on_event(&self, event: &ProgressEvent) {
+        self(event)
+    }
+}
+
+/// 取消标记，克隆出的标记共享同一个状态
+#[derive(Clone, Default, Debug)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// 传给封装、解封装的进度上下文：每处理完一个二级数据块通知观察者，并在二级数据块之间检查是否已被取消
+///
+/// 被取消时返回 PosError::Cancelled；原地封装、解封装被取消后扇区文件停留在中间状态
+#[derive(Clone, Default)]
+pub struct Progress {
+    observer: Option<Arc<dyn ProgressObserver>>,
+    token: CancelToken,
+}
+
+impl Progress {
+    pub fn new<O: ProgressObserver + 'static>(observer: O) -> (Progress, CancelToken) {
+        //! 返回进度上下文及用于取消的标记
+        let progress = Progress { observer: Some(Arc::new(observer)), token: CancelToken::default() };
+        let token = progress.token();
+        (progress, token)
+    }
+
+    pub fn with_token<O: ProgressObserver + 'static>(observer: O, token: CancelToken) -> Progress {
+        //! 由已有的取消标记控制，多个进度上下文可以共用同一个标记
+        Progress { observer: Some(Arc::new(observer)), token }
+    }
+
+    pub fn from_token(token: CancelToken) -> Progress {
+        //! 不通知观察者，只由 token 取消
+        Progress { observer: None, token }
+    }
+
+    pub fn none() -> Progress {
+        //! 不通知任何观察者，也不会被取消
+        Progress::default()
+    }
+
+    pub fn silent(&self) -> Progress {
+        //! 共用同一个取消标记，但不通知观察者，用于由调用者自行汇总进度的内部步骤
+        Progress { observer: None, token: self.token() }
+    }
+
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.token.is_cancelled() {
+            return Err(PosError::Cancelled);
+        }
+        Ok(())
+    }
+
+    pub fn start(&self, phase: Phase, total: usize) -> Instant {
+        self.notify(&ProgressEvent::PhaseStarted { phase, total });
+        Instant::now()
+    }
+
+    pub fn block_done(&self, phase: Phase, idx2: usize, done: usize, total: usize) -> Result<()> {
+        self.notify(&ProgressEvent::BlockDone { phase, idx2, done, total });
+        self.check()
+    }
+
+    pub fn io(&self, phase: Phase, bytes: u64) {
+        self.notify(&ProgressEvent::Io { phase, bytes });
+    }
+
+    pub fn finish(&self, phase: Phase, start: Instant) {
+        self.notify(&ProgressEvent::PhaseFinished { phase, elapsed: start.elapsed() });
+    }
+
+    fn notify(&self, event: &ProgressEvent) {
+        if let Some(observer) = &self.observer {
+            observer.on_event(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_progress() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let recorder = events.clone();
+        let (progress, token) = Progress::new(move |event: &ProgressEvent| recorder.lock().unwrap().push(event.clone()));
+
+        let start = progress.start(Phase::Seal, 2);
+        progress.block_done(Phase::Seal, 0, 1, 2).unwrap();
+        token.cancel();
+        assert!(matches!(progress.block_done(Phase::Seal, 1, 2, 2), Err(PosError::Cancelled)));
+        progress.finish(Phase::Seal, start);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], ProgressEvent::PhaseStarted { phase: Phase::Seal, total: 2 });
+        assert_eq!(events[2], ProgressEvent::BlockDone { phase: Phase::Seal, idx2: 1, done: 2, total: 2 });
+        assert!(matches!(events[3], ProgressEvent::PhaseFinished { phase: Phase::Seal, .. }));
+        assert!(Progress::none().check().is_ok());
+    }
+}
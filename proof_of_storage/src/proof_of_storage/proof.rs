@@ -0,0 +1,108 @@
+use serde::{Serialize, Deserialize};
+
+use super::common::blake3_hash;
+use super::error::{PosError, Result};
+use super::merkle_tree::verify_merkle_proof_bytes;
+
+/// 证明编码格式版本，位于 to_bytes 结果的第一个字节
+pub const PROOF_VERSION: u8 = 1;
+
+/// 对挑战的应答，可以保存下来或发送给另一个进程验证
+///
+/// indices / blocks 为被挑战的封装后二级数据块；depend_indices / depend_blocks 为解封装它们还需要的
+/// 前一个二级数据块及长程依赖（升序，不含被挑战的二级数据块，只有复制证明使用），
+/// 两者合并后按编号升序由 comm_r_proof 证明属于 comm_r；
+/// comm_d_proof 证明被挑战的二级数据块解封装后属于 comm_d，只有复制证明使用
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct StorageProof {
+    // 生成证明时使用的参数编号，见 PosPara::id
+    pub params_id: [u8; 32],
+    pub indices: Vec<usize>,
+    pub blocks: Vec<Vec<u8>>,
+    pub depend_indices: Vec<usize>,
+    pub depend_blocks: Vec<Vec<u8>>,
+    pub comm_r_proof: Vec<u8>,
+    pub comm_d_proof: Option<Vec<u8>>,
+}
+
+impl StorageProof {
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        //! 版本号（1 字节）后接 bincode 编码
+        let mut res = vec![PROOF_VERSION];
+        res.append(&mut bincode::serialize(self).map_err(|err| PosError::InvalidParams(err.to_string()))?);
+        Ok(res)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<StorageProof> {
+        match bytes.split_first() {
+            Some((&PROOF_VERSION, body)) => bincode::deserialize(body).map_err(|err| PosError::InvalidProof(format!("bad proof encoding: {}", err))),
+            Some((version, _)) => Err(PosError::InvalidProof(format!("unsupported proof version {}, expected {}", version, PROOF_VERSION))),
+            None => Err(PosError::InvalidProof("empty proof".to_string())),
+        }
+    }
+
+    pub fn opened(&self) -> Vec<(usize, &Vec<u8>)> {
+        //! 被打开的全部封装后二级数据块，按编号升序
+        let mut res: Vec<(usize, &Vec<u8>)> = self.indices.iter().copied().zip(self.blocks.iter())
+        .chain(self.depend_indices.iter().copied().zip(self.depend_blocks.iter()))
+        .collect();
+        res.sort_by_key(|&(idx2, _)| idx2);
+        res
+    }
+
+    pub fn check_shape(&self, params_id: &[u8; 32], indices: &Vec<usize>) -> Result<()> {
+        //! 参数编号、应答的挑战以及各字段长度是否一致
+        if self.params_id != *params_id {
+            return Err(PosError::InvalidProof("proof was generated with other params".to_string()));
+        }
+        if self.indices != *indices {
+            return Err(PosError::InvalidProof(format!("proof answers blocks {:?}, challenge asked for {:?}", self.indices, indices)));
+        }
+        if self.blocks.len() != self.indices.len() || self.depend_blocks.len() != self.depend_indices.len() {
+            return Err(PosError::InvalidProof("numbers of opened blocks and indices differ".to_string()));
+        }
+        if self.opened().windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(PosError::InvalidProof("a block is opened more than once".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn verify_comm_r(&self, comm_r: [u8; 32], block_cnt: usize) -> Result<()> {
+        //! 被打开的全部二级数据块属于 comm_r
+        let (opened_indices, leaf_values): (Vec<usize>, Vec<Vec<u8>>) = self.opened().into_iter().map(|(idx2, block)| (idx2, blake3_hash(block))).unzip();
+        verify_merkle_proof_bytes(&self.comm_r_proof, comm_r, &opened_indices, &leaf_values, block_cnt)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_proof_bytes() {
+        let proof = StorageProof {
+            params_id: [1u8; 32],
+            indices: vec![1, 4],
+            blocks: vec![vec![1, 2], vec![3, 4]],
+            depend_indices: vec![0, 3],
+            depend_blocks: vec![vec![5, 6], vec![7, 8]],
+            comm_r_proof: vec![9; 64],
+            comm_d_proof: Some(vec![10; 32]),
+        };
+        let bytes = proof.to_bytes().unwrap();
+        assert_eq!(bytes[0], PROOF_VERSION);
+        assert_eq!(StorageProof::from_bytes(&bytes).unwrap(), proof);
+        assert_eq!(proof.opened().iter().map(|&(idx2, _)| idx2).collect::<Vec<usize>>(), vec![0, 1, 3, 4]);
+        proof.check_shape(&[1u8; 32], &vec![1, 4]).unwrap();
+        assert!(proof.check_shape(&[2u8; 32], &vec![1, 4]).is_err());
+
+        let mut other = bytes.clone();
+        other[0] = PROOF_VERSION + 1;
+        assert!(matches!(StorageProof::from_bytes(&other), Err(PosError::InvalidProof(_))));
+        assert!(matches!(StorageProof::from_bytes(&bytes[..bytes.len() / 2]), Err(PosError::InvalidProof(_))));
+        assert!(StorageProof::from_bytes(&[]).is_err());
+
+        let twice = StorageProof { depend_indices: vec![0, 4], ..proof };
+        assert!(twice.check_shape(&[1u8; 32], &vec![1, 4]).is_err());
+    }
+}
@@ -1,306 +1,349 @@
 use rug::Integer;
-use std::{fs::OpenOptions, io::{Write, Seek, SeekFrom}, time::Instant};
+use std::str::FromStr;
+use std::{fs::{File, OpenOptions}, io::{Write, Seek, SeekFrom}, time::Instant};
 
 use crate::{vde::rug_vde::{vde, vde_inv}};
 
-use super::{depend::{long_depend, short_depend, short_depend_random, long_mode_random}, postorage::PosPara};
+use super::params::{PosPara, prime_for_unit};
+use super::cache::BlockCache;
 use super::common::{read_file, to_units, com_units, modadd, modsub, blake3_hash};
+use super::error::{PosError, Result};
+use super::merkle_tree::generate_merkle_tree_from_data;
+use super::mmap::SectorAccess;
+use super::progress::{Phase, Progress};
+use super::replica::ReplicaId;
+use super::sector::{Commitments, SectorHeader, SECTOR_HEADER_LEN, data_offset, read_header, write_header, write_origin_leaves, write_sealed_leaves};
+use super::stats::{SealStats, UnsealStats};
+
+pub fn prepare_params(unit_pl: usize) -> Result<Integer> {
+    // 生成vde需要的key，封装iv由副本编号导出，见 ReplicaId
+    let vde_key = Integer::from_str(prime_for_unit(unit_pl)?).unwrap();
+    Ok(vde_key)
+}
 
-pub fn create_long_depend(num: usize, count: usize, mode: usize) -> Vec<Vec<usize>> {
-    let mut indices = vec![];
-    for idx in 0..num {
-        let cur_indices = long_depend(idx, count, mode);
-        indices.push(cur_indices);
+pub fn check_file_len(file: &File, expected: usize) -> Result<()> {
+    //! 检查封装文件长度是否至少为 expected 字节
+    let len = file.metadata()?.len();
+    if len < expected as u64 {
+        return Err(PosError::CorruptData(format!("sealed file has {} bytes, expected at least {}", len, expected)));
     }
-    indices
+    Ok(())
 }
 
-pub fn create_short_depend(num: usize, count: usize, mode: usize) -> Vec<Vec<usize>> {
-    let mut indices = vec![];
-    for idx in 0..num {
-        let cur_indices = short_depend(num, idx, count, mode);
-        indices.push(cur_indices);
-    }
-    indices
+// 基于文件的封装流程统计各部分耗时，供实验驱动程序及 SealScheduler 使用，单个扇区的对外接口使用 stream::seal_stream
+#[cfg(any(test, feature = "experiments"))]
+pub fn copy_and_pad(origin_path: &str, new_path: &str, params: &PosPara) -> Result<[u8; 32]> {
+    //! 将原始文件按照 L1 大小逐个pad（在高位添加一个 0），再存储到新文件，返回 pad 后数据的 comm_d
+    //!
+    //! 原始文件不足 params.block_count() 个二级数据块的部分补 0，真实长度写入文件头部
+    copy_and_pad_with_progress(origin_path, new_path, params, &Progress::none())
 }
 
-pub fn copy_and_pad(origin_path: &str, new_path: &str, data_l: usize, unit_l: usize) {
-    //! 将原始文件按照 L1 大小逐个pad（在高位添加一个 0），再存储到新文件
+pub fn copy_and_pad_with_progress(origin_path: &str, new_path: &str, params: &PosPara, progress: &Progress) -> Result<[u8; 32]> {
+    //! 同 copy_and_pad，每写入一个二级数据块通知 progress 读写的字节数，并检查是否已被取消
     let mut origin_file = OpenOptions::new()
     .read(true)
-    .open(origin_path)
-    .unwrap();
+    .open(origin_path)?;
+
+    let data_len = origin_file.metadata()?.len();
+    if data_len > params.data_l as u64 {
+        return Err(PosError::InvalidParams(format!("{} is {} bytes, params allow at most {} bytes", origin_path, data_len, params.data_l)));
+    }
 
     let mut new_file = OpenOptions::new()
     .read(true)
     .write(true)
     .create(true)
     .truncate(true)
-    .open(new_path)
-    .unwrap();
-
-    let block_cnt = data_l / unit_l;
-    for cnt in 0..block_cnt {
-        let mut buf = read_file(&mut origin_file, cnt * unit_l, unit_l);
-        buf.push(0);
-        new_file.write_all(&buf).unwrap();
+    .open(new_path)?;
+
+    // 预留文件头部
+    new_file.write_all(&vec![0u8; SECTOR_HEADER_LEN])?;
+
+    let unit_l = params.unit_l;
+    let block_cnt = params.block_count();
+    let mut origin_blocks_id = vec![];
+    let pad_start = progress.start(Phase::Pad, block_cnt);
+    for idx2 in 0..block_cnt {
+        let mut block = Vec::with_capacity(params.block_pl);
+        // 从原始文件读出的字节数
+        let mut bytes_read = 0;
+        for idx1 in 0..params.block_l / unit_l {
+            let mut buf = vec![0u8; unit_l];
+            let begin = idx2 * params.block_l + idx1 * unit_l;
+            if (begin as u64) < data_len {
+                let len = usize::min(unit_l, data_len as usize - begin);
+                buf[..len].copy_from_slice(&read_file(&mut origin_file, begin, len)?);
+                bytes_read += len;
+            }
+            buf.push(0);
+            block.append(&mut buf);
+        }
+        origin_blocks_id.push(blake3_hash(&block));
+        new_file.write_all(&block)?;
+        progress.io(Phase::Pad, (bytes_read + block.len()) as u64);
+        progress.block_done(Phase::Pad, idx2, idx2 + 1, block_cnt)?;
     }
+    progress.finish(Phase::Pad, pad_start);
+    write_origin_leaves(&mut new_file, params, &origin_blocks_id)?;
+    let (_, _, comm_d) = generate_merkle_tree_from_data(&origin_blocks_id)?;
+
+    // 真实长度与 comm_d 先写入文件头部，封装完成后写入完整内容
+    let header = SectorHeader {
+        params: params.clone(),
+        prime: String::new(),
+        prover_id: [0u8; 32],
+        sector_num: 0,
+        ticket: [0u8; 32],
+        data_len,
+        comm_d,
+        comm_r: [0u8; 32],
+        sealed: false,
+    };
+    write_header(&mut new_file, &header)?;
+    Ok(comm_d)
 }
 
-pub fn seal(params: &PosPara, path: &str, vde_key: &Integer, iv: &Vec<u8>) -> (Vec<Vec<u8>>, f32, f32, f32, f32, f32, f32) {
+pub fn seal(params: &PosPara, path: &str, vde_key: &Integer, replica: &ReplicaId, cache: &mut BlockCache, progress: &Progress) -> Result<(Commitments, Vec<Vec<u8>>, SealStats)> {
+    //! 原地封装 copy_and_pad 生成的文件，replica.comm_d 须与 copy_and_pad 返回的 comm_d 一致
+    //!
+    //! comm_d 与 comm_r 在封装的同一遍中计算，并写入文件头部
+    //!
+    //! 封装后的二级数据块放入 cache，长程依赖优先从 cache 中读取
+    params.validate()?;
+
     let mut file = OpenOptions::new()
     .read(true)
     .write(true)
-    .open(path)
-    .unwrap();
+    .open(path)?;
 
-    let mut vde_cost = 0.0;
-    let mut file_cost = 0.0;
-    let mut depend_cost = 0.0;
-    let mut hash_cost = 0.0;
-    let mut block_cost = 0.0;
-    let mut modadd_cost = 0.0;
+    let mut stats = SealStats::default();
+    let (hits, misses) = (cache.hits(), cache.misses());
+
+    // 原始数据的真实长度及 comm_d 由 copy_and_pad 写入文件头部
+    let padded_header = read_header(&mut file)?;
+    if padded_header.comm_d != replica.comm_d {
+        return Err(PosError::InvalidParams("replica id was derived from another comm_d".to_string()));
+    }
+    let data_len = padded_header.data_len;
+    let iv = replica.iv();
 
     // block_cnt: 二级数据块个数
-    let block_cnt = params.data_l / params.block_l;
+    let block_cnt = params.block_count();
     let mut blocks_id = vec![vec![]; block_cnt];
-
-    let start = Instant::now();
-    let idxs_l = {
-        if params.mode_l != 0 {
-            create_long_depend(block_cnt, params.cnt_l, params.mode_l)
-        }
-        else {
-            vec![]
-        }
-    };
-    let idxs_s = {
-        if params.mode_s != 0 {
-            create_short_depend(block_cnt, params.cnt_s, params.mode_s)
-        }
-        else {
-            vec![]
-        }
-    };
-    depend_cost += start.elapsed().as_secs_f32();
+    // 封装前各二级数据块的哈希值，用于计算 comm_d
+    let mut origin_blocks_id = vec![vec![]; block_cnt];
+    let mut sector = SectorAccess::open(path, params, true)?;
 
     // 逐个封装二级数据块
+    let seal_start = progress.start(Phase::Seal, block_cnt);
     for idx2 in 0..block_cnt {
+        // 此前读写扇区文件的字节数，当前二级数据块完成后通知 progress 其间读写的字节数
+        let io_bytes = stats.bytes_read + stats.bytes_written;
         let mut cur_block = {
             let start = Instant::now();
-            let buf = read_file(&mut file, idx2 * params.block_pl, params.block_pl);
-            file_cost += start.elapsed().as_secs_f32();
+            let buf = sector.block(idx2)?;
+            stats.file += start.elapsed().as_secs_f32();
+            stats.bytes_read += params.block_pl as u64;
 
             let start = Instant::now();
-            let block = to_units(&buf, params.unit_pl);
-            block_cost += start.elapsed().as_secs_f32();
+            origin_blocks_id[idx2] = blake3_hash(buf);
+            stats.hash += start.elapsed().as_secs_f32();
+            stats.hash_calls += 1;
+
+            let start = Instant::now();
+            let block = sector.unit_vecs(idx2)?;
+            stats.block += start.elapsed().as_secs_f32();
             block
         };
 
-        let unit_cnt = cur_block.len();
-
         // 当前二级数据块长程依赖的二级数据块集合
         let depend_blocks = {
-            let mut res = vec![];
-            if params.mode_l == 0 {
-                // let before_unit = {
-                //     if idx2 != 0 {
-                //         let start = Instant::now();
-                //         let buf = read_file(&mut file, idx2 * BLOCK_PL - UNIT_PL, UNIT_PL);
-                //         file_cost += start.elapsed().as_secs_f32();
-                //         buf
-                //     }
-                //     else {
-                //         vec![]
-                //     }
-                // };
-                // let start = Instant::now();
-                // let cur_idxs_l = long_mode_random(&before_unit, idx2, cnt_l);
-                // depend_cost += start.elapsed().as_secs_f32();
-                let start = Instant::now();
-                let cur_idxs_l = {
-                    if idx2 == 0 {
-                        vec![]
-                    }
-                    else {
-                        if params.cnt_l == 0 {
-                            long_mode_random(block_cnt, &blocks_id[idx2 - 1], idx2, idx2 / 10 + 1)
-                        }
-                        else {
-                            long_mode_random(block_cnt, &blocks_id[idx2 - 1], idx2, params.cnt_l)
-                        }
-                    }
-                };
-                depend_cost += start.elapsed().as_secs_f32();
-
-                for &i in &cur_idxs_l {
-                    let start = Instant::now();
-                    let buf = read_file(&mut file, i * params.block_pl, params.block_pl);
-                    file_cost += start.elapsed().as_secs_f32();
+            let start = Instant::now();
+            let cur_idxs_l = {
+                if idx2 == 0 {
+                    vec![]
+                }
+                else {
+                    params.long_depend.indices(block_cnt, idx2, &blocks_id[idx2 - 1])
+                }
+            };
+            stats.depend += start.elapsed().as_secs_f32();
 
+            let mut res = vec![];
+            for &i in &cur_idxs_l {
+                let ans = cache.get_or_load(i, || {
                     let start = Instant::now();
-                    let ans = to_units(&buf, params.unit_pl);
-                    block_cost += start.elapsed().as_secs_f32();
+                    let ans = sector.unit_vecs(i)?;
+                    stats.file += start.elapsed().as_secs_f32();
+                    stats.bytes_read += params.block_pl as u64;
+                    Ok(ans)
+                })?;
+                res.push(ans);
+            }
+            res
+        };
 
-                    res.push(ans);
-                }
+        // 第 0 个二级数据块以 iv 为链接哈希，其余以前一个封装后二级数据块的哈希值为链接哈希
+        let chain_id = {
+            if idx2 == 0 {
+                iv.clone()
             }
             else {
-                for &i in &idxs_l[idx2] {
-                    let start = Instant::now();
-                    let buf = read_file(&mut file, i * params.block_pl, params.block_pl);
-                    file_cost += start.elapsed().as_secs_f32();
-
-                    let start = Instant::now();
-                    let ans = to_units(&buf, params.unit_pl);
-                    block_cost += start.elapsed().as_secs_f32();
-
-                    res.push(ans);
-                }
+                blocks_id[idx2 - 1].clone()
             }
-            res
         };
+        seal_block(params, &mut cur_block, &depend_blocks, &chain_id, vde_key, Some(&mut stats));
 
-        // 封装seal_rounds轮
-        for _ in 0..params.seal_rounds {
-            // 对当前二级数据块中的一级数据块逐个封装
-            for idx1 in 0..unit_cnt {
-                let mut depend_data = {
-                    let mut res = vec![];
-                    for i in 0..depend_blocks.len() {
-                        res.append(&mut depend_blocks[i][idx1].clone());
-                    }
+        let start = Instant::now();
+        sector.write_units(idx2, &cur_block)?;
+        stats.file += start.elapsed().as_secs_f32();
+        stats.bytes_written += params.block_pl as u64;
 
-                    if params.mode_s != 0 {
-                        for &idx in &idxs_s[idx1] {
-                            res.append(&mut cur_block[idx].clone());
-                        }
-                    }
-                    else {
-                        let start = Instant::now();
-                        let ans = {
-                            if idx1 == 0 {
-                                short_depend_random(unit_cnt, &vec![], idx1, params.cnt_s)
-                            }
-                            else {
-                                short_depend_random(unit_cnt, &cur_block[idx1-1], idx1, params.cnt_s)
-                            }
-                        };
-                        depend_cost += start.elapsed().as_secs_f32();
-
-                        for idx in ans {
-                            res.append(&mut cur_block[idx].clone());
-                        }
-                    }
-                    res
-                };
+        let start = Instant::now();
+        blocks_id[idx2] = blake3_hash(sector.block(idx2)?);
+        stats.hash += start.elapsed().as_secs_f32();
+        stats.hash_calls += 1;
+        cache.insert(idx2, cur_block);
 
-                if idx1 == 0 {
-                    if idx2 == 0 {
-                        depend_data.append(&mut iv.clone());
-                    }
-                    else {
-                        depend_data.append(&mut blocks_id[idx2 - 1].clone());
-                    }
-                }
+        progress.io(Phase::Seal, stats.bytes_read + stats.bytes_written - io_bytes);
+        progress.block_done(Phase::Seal, idx2, idx2 + 1, block_cnt)?;
+    }
+    stats.elapsed = seal_start.elapsed().as_secs_f32();
+    stats.cache_hits = cache.hits() - hits;
+    stats.cache_misses = cache.misses() - misses;
+    progress.finish(Phase::Seal, seal_start);
+    sector.flush()?;
+
+    // 写入 comm_r 的叶子及文件头部，记录解封装所需的全部参数
+    let (_, _, comm_d) = generate_merkle_tree_from_data(&origin_blocks_id)?;
+    let (_, _, comm_r) = generate_merkle_tree_from_data(&blocks_id)?;
+    write_sealed_leaves(&mut file, params, &blocks_id)?;
+    let header = SectorHeader {
+        params: params.clone(),
+        prime: vde_key.to_string(),
+        prover_id: replica.prover_id,
+        sector_num: replica.sector_num,
+        ticket: replica.ticket,
+        data_len,
+        comm_d,
+        comm_r,
+        sealed: true,
+    };
+    write_header(&mut file, &header)?;
 
-                // 长程依赖及短程依赖数据的哈希值
-                let start = Instant::now();
-                let depend_data_hash = blake3_hash(&depend_data);
-                hash_cost += start.elapsed().as_secs_f32();
+    Ok((header.commitments(), blocks_id, stats))
+}
 
-                // 当前一级数据块记为cur_unit
-                let cur_unit = &cur_block[idx1].to_vec();
+pub fn seal_block(params: &PosPara, cur_block: &mut Vec<Vec<u8>>, depend_blocks: &Vec<Vec<Vec<u8>>>, chain_id: &Vec<u8>, vde_key: &Integer, mut stats: Option<&mut SealStats>) {
+    //! 封装一个（已 pad 的）二级数据块，seal 及流式封装、修复共用
+    //!
+    //! chain_id: 第 0 个二级数据块为 iv，其余为前一个封装后二级数据块的哈希值
+    //!
+    //! stats 不为 None 时累计短程依赖、哈希、模加及 vde 的耗时与调用次数
+    let unit_cnt = cur_block.len();
+    // 封装seal_rounds轮
+    for _ in 0..params.seal_rounds {
+        // 对当前二级数据块中的一级数据块逐个封装
+        for idx1 in 0..unit_cnt {
+            let mut depend_data = vec![];
+            for depend_block in depend_blocks {
+                depend_data.extend_from_slice(&depend_block[idx1]);
+            }
+            let start = Instant::now();
+            let idxs_s = params.short_depend.indices(cur_block, idx1);
+            let depend_time = start.elapsed().as_secs_f32();
+            for idx in idxs_s {
+                depend_data.extend_from_slice(&cur_block[idx]);
+            }
+            if idx1 == 0 {
+                depend_data.extend_from_slice(chain_id);
+            }
 
-                // 哈希值与一级数据块异或
-                let start = Instant::now();
-                let unit_modadd = modadd(&cur_unit, &depend_data_hash, &vde_key);
-                modadd_cost += start.elapsed().as_secs_f32();
+            // 长程依赖及短程依赖数据的哈希值
+            let start = Instant::now();
+            let depend_data_hash = blake3_hash(&depend_data);
+            let hash_time = start.elapsed().as_secs_f32();
 
-                // 将异或结果带入vde计算得到new_unit
-                let start = Instant::now();
-                let new_unit = vde(&unit_modadd, &vde_key, params.vde_rounds, &params.vde_mode, params.unit_pl);
-                vde_cost += start.elapsed().as_secs_f32();
+            // 哈希值与一级数据块模加
+            let start = Instant::now();
+            let unit_modadd = modadd(&cur_block[idx1], &depend_data_hash, vde_key);
+            let modadd_time = start.elapsed().as_secs_f32();
 
-                // 更新unit的值
-                cur_block[idx1] = new_unit;
+            // 将模加结果带入vde计算得到新的一级数据块
+            let start = Instant::now();
+            cur_block[idx1] = vde(&unit_modadd, vde_key, params.vde_rounds, params.vde_mode, params.unit_pl);
+            let vde_time = start.elapsed().as_secs_f32();
+
+            if let Some(stats) = stats.as_deref_mut() {
+                stats.depend += depend_time;
+                stats.hash += hash_time;
+                stats.hash_calls += 1;
+                stats.modadd += modadd_time;
+                stats.vde += vde_time;
+                stats.vde_calls += 1;
             }
         }
-
-        let start = Instant::now();
-        let cur_block = com_units(&cur_block);
-        block_cost += start.elapsed().as_secs_f32();
-
-        let start = Instant::now();
-        blocks_id[idx2] = blake3_hash(&cur_block);
-        hash_cost += start.elapsed().as_secs_f32();
-
-        let start = Instant::now();
-        file.seek(SeekFrom::Start((idx2 * params.block_pl).try_into().unwrap())).unwrap();
-        file.write_all(&cur_block).unwrap();
-        file_cost += start.elapsed().as_secs_f32();
     }
-
-    (blocks_id, vde_cost, file_cost, depend_cost, hash_cost, block_cost, modadd_cost)
 }
 
-pub fn copy_and_compress(origin_path: &str, new_path: &str, data_l: usize, unit_l: usize, unit_pl: usize) {
+pub fn copy_and_compress(origin_path: &str, new_path: &str, data_l: usize, unit_l: usize, unit_pl: usize) -> Result<()> {
+    //! 将解封装后的文件逐个去掉一级数据块高位的 pad，再存储到新文件
     let mut origin_file = OpenOptions::new()
     .read(true)
-    .open(origin_path)
-    .unwrap();
+    .open(origin_path)?;
 
     let mut new_file = OpenOptions::new()
     .read(true)
     .write(true)
     .create(true)
     .truncate(true)
-    .open(new_path)
-    .unwrap();
+    .open(new_path)?;
+
+    let header = read_header(&mut origin_file)?;
+    if header.sealed {
+        return Err(PosError::InvalidParams(format!("{} is still sealed, unseal it first", origin_path)));
+    }
 
-    let block_cnt = data_l / unit_l;
-    for cnt in 0..block_cnt {
-        let buf = read_file(&mut origin_file, cnt * unit_pl, unit_pl);
-        new_file.write_all(&buf[0..unit_l]).unwrap();
+    // 只写出原始数据的真实长度，末尾补的 0 须保持为 0
+    let data_len = header.data_len as usize;
+    if data_len > data_l {
+        return Err(PosError::CorruptData(format!("sector records {} bytes of data, params allow at most {}", data_len, data_l)));
     }
+    let unit_cnt = data_len.div_ceil(unit_l);
+    for cnt in 0..unit_cnt {
+        let buf = read_file(&mut origin_file, data_offset(cnt * unit_pl), unit_pl)?;
+        let len = usize::min(unit_l, data_len - cnt * unit_l);
+        // pad 位不为 0，说明解封装结果不正确
+        if buf[len..].iter().any(|&b| b != 0) {
+            return Err(PosError::CorruptData(format!("unit {} has non-zero padding after unseal", cnt)));
+        }
+        new_file.write_all(&buf[0..len])?;
+    }
+    Ok(())
 }
 
-pub fn unseal(params: &PosPara, path: &str, vde_key: &Integer, iv: &Vec<u8>) 
--> (f32, f32, f32, f32, f32, f32) {
+pub fn unseal(params: &PosPara, path: &str, progress: &Progress) -> Result<UnsealStats> {
+    //! 原地解封装，vde key 与 iv 从文件头部读取
+    params.validate()?;
+
     let mut file = OpenOptions::new()
     .read(true)
     .write(true)
-    .open(path)
-    .unwrap();
-
-    let mut vde_cost = 0.0;
-    let mut file_cost = 0.0;
-    let mut depend_cost = 0.0;
-    let mut hash_cost = 0.0;
-    let mut block_cost = 0.0;
-    let mut modsub_cost = 0.0;
-
-    let block_cnt = params.data_l / params.block_l;
-
-    let start = Instant::now();
-    let idxs_l = {
-        if params.mode_l != 0 {
-            create_long_depend(block_cnt, params.cnt_l, params.mode_l)
-        }
-        else {
-            vec![]
-        }
-    };
-    let idxs_s = {
-        if params.mode_s != 0 {
-            create_short_depend(block_cnt, params.cnt_s, params.mode_s)
-        }
-        else {
-            vec![]
-        }
-    };
-    depend_cost += start.elapsed().as_secs_f32();
+    .open(path)?;
 
+    let mut header = read_header(&mut file)?;
+    header.check_params(params)?;
+    header.check_sealed()?;
+    let vde_key = header.vde_key()?;
+    let iv = header.iv();
+
+    let mut stats = UnsealStats::default();
+
+    let block_cnt = params.block_count();
+    check_file_len(&file, data_offset(block_cnt * params.block_pl))?;
+
+    let unseal_start = progress.start(Phase::Unseal, block_cnt);
     for i in 0..block_cnt {
         let idx2 = block_cnt - 1 - i;
         
@@ -308,11 +351,16 @@ pub fn unseal(params: &PosPara, path: &str, vde_key: &Integer, iv: &Vec<u8>)
             if idx2 != 0 {
                 let before_block = {
                     let start = Instant::now();
-                    let block = read_file(&mut file, (idx2 - 1) * params.block_pl, params.block_pl);
-                    file_cost += start.elapsed().as_secs_f32();
+                    let block = read_file(&mut file, data_offset((idx2 - 1) * params.block_pl), params.block_pl)?;
+                    stats.file += start.elapsed().as_secs_f32();
+                    stats.bytes_read += params.block_pl as u64;
                     block
                 };
-                blake3_hash(&before_block)
+                let start = Instant::now();
+                let before_block_id = blake3_hash(&before_block);
+                stats.hash += start.elapsed().as_secs_f32();
+                stats.hash_calls += 1;
+                before_block_id
             }
             else {
                 vec![]
@@ -321,74 +369,35 @@ pub fn unseal(params: &PosPara, path: &str, vde_key: &Integer, iv: &Vec<u8>)
 
         let mut cur_block = {
             let start = Instant::now();
-            let buf = read_file(&mut file, idx2 * params.block_pl, params.block_pl);
-            file_cost += start.elapsed().as_secs_f32();
+            let buf = read_file(&mut file, data_offset(idx2 * params.block_pl), params.block_pl)?;
+            stats.file += start.elapsed().as_secs_f32();
+            stats.bytes_read += params.block_pl as u64;
             
             let start = Instant::now();
             let block = to_units(&buf, params.unit_pl);
-            block_cost += start.elapsed().as_secs_f32();
+            stats.block += start.elapsed().as_secs_f32();
             block
         };
 
         let unit_cnt = cur_block.len();
 
         let depend_blocks = {
+            let start = Instant::now();
+            let cur_idxs_l = params.long_depend.indices(block_cnt, idx2, &before_block_id);
+            stats.depend += start.elapsed().as_secs_f32();
+
             let mut res = vec![];
-            if params.mode_l == 0 {
-                // let before_unit = {
-                //     if idx2 != 0 {
-                //         let start = Instant::now();
-                //         let buf = read_file(&mut file, idx2 * BLOCK_PL - UNIT_PL, UNIT_PL);
-                //         file_cost += start.elapsed().as_secs_f32();
-                //         buf
-                //     }
-                //     else {
-                //         vec![]
-                //     }
-                // };
-                // let start = Instant::now();
-                // let cur_idxs_l = long_mode_random(&before_unit, idx2, cnt_l);
-                // depend_cost += start.elapsed().as_secs_f32();
+            for &i in &cur_idxs_l {
                 let start = Instant::now();
-                let cur_idxs_l = {
-                    if idx2 == 0 {
-                        vec![]
-                    }
-                    else {
-                        if params.cnt_l == 0 {
-                            long_mode_random(block_cnt, &before_block_id, idx2, idx2 / 10 + 1)
-                        }
-                        else {
-                            long_mode_random(block_cnt, &before_block_id, idx2, params.cnt_l)
-                        }
-                    }
-                };
-                depend_cost += start.elapsed().as_secs_f32();
-
-                for i in 0..cur_idxs_l.len() {
-                    let start = Instant::now();
-                    let buf = read_file(&mut file, cur_idxs_l[i] * params.block_pl, params.block_pl);
-                    file_cost += start.elapsed().as_secs_f32();
+                let buf = read_file(&mut file, data_offset(i * params.block_pl), params.block_pl)?;
+                stats.file += start.elapsed().as_secs_f32();
+                stats.bytes_read += params.block_pl as u64;
 
-                    let start = Instant::now();
-                    let ans = to_units(&buf, params.unit_pl);
-                    block_cost += start.elapsed().as_secs_f32();
+                let start = Instant::now();
+                let ans = to_units(&buf, params.unit_pl);
+                stats.block += start.elapsed().as_secs_f32();
 
-                    res.push(ans);
-                }
-            }
-            else {
-                for i in 0..idxs_l[idx2].len() {
-                    let start = Instant::now();
-                    let buf = read_file(&mut file, i * params.block_pl, params.block_pl);
-                    file_cost += start.elapsed().as_secs_f32();
-
-                    let start = Instant::now();
-                    let ans = to_units(&buf, params.unit_pl);
-                    block_cost += start.elapsed().as_secs_f32();
-
-                    res.push(ans);
-                }
+                res.push(ans);
             }
             res
         };
@@ -403,26 +412,12 @@ pub fn unseal(params: &PosPara, path: &str, vde_key: &Integer, iv: &Vec<u8>)
                     for i in 0..depend_blocks.len() {
                         res.append(&mut depend_blocks[i][idx1].clone());
                     }
-                    if params.mode_s != 0 {
-                        for &idx in &idxs_s[idx1] {
-                            res.append(&mut cur_block[idx].clone());
-                        }
-                    }
-                    else {
-                        let start = Instant::now();
-                        let ans = {
-                            if idx1 == 0 {
-                                short_depend_random(unit_cnt, &vec![], idx1, params.cnt_s)
-                            }
-                            else {
-                                short_depend_random(unit_cnt, &cur_block[idx1-1], idx1, params.cnt_s)
-                            }
-                        };
-                        depend_cost += start.elapsed().as_secs_f32();
-
-                        for idx in ans {
-                            res.append(&mut cur_block[idx].clone());
-                        }
+                    let start = Instant::now();
+                    let idxs_s = params.short_depend.indices(&cur_block, idx1);
+                    stats.depend += start.elapsed().as_secs_f32();
+
+                    for idx in idxs_s {
+                        res.append(&mut cur_block[idx].clone());
                     }
                     res
                 };
@@ -438,17 +433,19 @@ pub fn unseal(params: &PosPara, path: &str, vde_key: &Integer, iv: &Vec<u8>)
 
                 let start = Instant::now();
                 let depend_data_hash = blake3_hash(&depend_data);
-                hash_cost += start.elapsed().as_secs_f32();
+                stats.hash += start.elapsed().as_secs_f32();
+                stats.hash_calls += 1;
 
                 let cur_unit = &cur_block[idx1].to_vec();
 
                 let start = Instant::now();
-                let vde_inv_res = vde_inv(&cur_unit, &vde_key, params.vde_rounds, &params.vde_mode, params.unit_pl);
-                vde_cost += start.elapsed().as_secs_f32();
+                let vde_inv_res = vde_inv(&cur_unit, &vde_key, params.vde_rounds, params.vde_mode, params.unit_pl);
+                stats.vde += start.elapsed().as_secs_f32();
+                stats.vde_calls += 1;
 
                 let start = Instant::now();
                 let new_unit = modsub(&vde_inv_res, &&depend_data_hash, &vde_key);
-                modsub_cost += start.elapsed().as_secs_f32();
+                stats.modsub += start.elapsed().as_secs_f32();
 
                 cur_block[idx1] = new_unit;
             }
@@ -456,17 +453,99 @@ pub fn unseal(params: &PosPara, path: &str, vde_key: &Integer, iv: &Vec<u8>)
 
         let start = Instant::now();
         let cur_block = com_units(&cur_block);
-        block_cost += start.elapsed().as_secs_f32();
+        stats.block += start.elapsed().as_secs_f32();
 
         // let start = Instant::now();
         // blocks_id[idx2] = blake3_hash(&cur_block);
         // hash_cost += start.elapsed().as_secs_f32();
 
         let start = Instant::now();
-        file.seek(SeekFrom::Start((idx2 * params.block_pl).try_into().unwrap())).unwrap();
-        file.write_all(&cur_block).unwrap();
-        file_cost += start.elapsed().as_secs_f32();
+        file.seek(SeekFrom::Start(data_offset(idx2 * params.block_pl) as u64))?;
+        file.write_all(&cur_block)?;
+        stats.file += start.elapsed().as_secs_f32();
+        stats.bytes_written += params.block_pl as u64;
+
+        progress.block_done(Phase::Unseal, idx2, i + 1, block_cnt)?;
     }
+    stats.elapsed = unseal_start.elapsed().as_secs_f32();
+    progress.finish(Phase::Unseal, unseal_start);
+
+    header.sealed = false;
+    write_header(&mut file, &header)?;
 
-    (vde_cost, file_cost, depend_cost, hash_cost, block_cost, modsub_cost)
-}
\ No newline at end of file
+    Ok(stats)
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proof_of_storage::params::preset;
+    use crate::proof_of_storage::test_util::{test_params_with_blocks, temp_path};
+
+    #[test]
+    fn test_seal_missing_file() {
+        let params = preset("1m").unwrap();
+        let vde_key = prepare_params(params.unit_pl).unwrap();
+        let replica = ReplicaId::new([0u8; 32], 0, [0u8; 32], [0u8; 32]);
+        let res = seal(&params, "no_such_dir/sealed_data", &vde_key, &replica, &mut BlockCache::disabled(), &Progress::none());
+        assert!(matches!(res, Err(PosError::Io(_))));
+    }
+
+    #[test]
+    fn test_seal_short_file() {
+        let params = preset("1m").unwrap();
+        let vde_key = prepare_params(params.unit_pl).unwrap();
+        let replica = ReplicaId::new([0u8; 32], 0, [0u8; 32], [0u8; 32]);
+        let path = std::env::temp_dir().join("pos_test_seal_short_file");
+        std::fs::write(&path, vec![0u8; params.block_pl]).unwrap();
+
+        let res = seal(&params, path.to_str().unwrap(), &vde_key, &replica, &mut BlockCache::disabled(), &Progress::none());
+        assert!(matches!(res, Err(PosError::CorruptData(_))));
+        let res = unseal(&params, path.to_str().unwrap(), &Progress::none());
+        assert!(matches!(res, Err(PosError::CorruptData(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_seal_bad_params() {
+        // 参数不合法时，在打开文件之前就返回错误
+        let mut params = preset("1m").unwrap();
+        params.block_l += 1;
+        let vde_key = prepare_params(params.unit_pl).unwrap();
+        let replica = ReplicaId::new([0u8; 32], 0, [0u8; 32], [0u8; 32]);
+        let res = seal(&params, "no_such_dir/sealed_data", &vde_key, &replica, &mut BlockCache::disabled(), &Progress::none());
+        assert!(matches!(res, Err(PosError::InvalidParams(_))));
+        assert!(matches!(prepare_params(params.unit_pl + 1), Err(PosError::InvalidParams(_))));
+    }
+
+    #[test]
+    fn test_seal_wrong_comm_d() {
+        let params = test_params_with_blocks(2);
+        let (origin, sealed) = (&temp_path("pos_test_seal_wrong_comm_d_origin"), &temp_path("pos_test_seal_wrong_comm_d_sealed"));
+        std::fs::write(origin, vec![5u8; 300]).unwrap();
+
+        let comm_d = copy_and_pad(origin, sealed, &params).unwrap();
+        let vde_key = prepare_params(params.unit_pl).unwrap();
+        let replica = ReplicaId::new([0u8; 32], 0, [0u8; 32], [0u8; 32]);
+        assert!(matches!(seal(&params, sealed, &vde_key, &replica, &mut BlockCache::disabled(), &Progress::none()), Err(PosError::InvalidParams(_))));
+        let replica = ReplicaId { comm_d, ..replica };
+        let (commitments, _, seal_stats) = seal(&params, sealed, &vde_key, &replica, &mut BlockCache::new(params.block_pl * 2), &Progress::none()).unwrap();
+        let units = (params.block_count() * params.block_l / params.unit_l * params.seal_rounds) as u64;
+        assert_eq!(seal_stats.vde_calls, units);
+        assert_eq!(seal_stats.bytes_written, (params.block_count() * params.block_pl) as u64);
+        let unseal_stats = unseal(&params, sealed, &Progress::none()).unwrap();
+        assert_eq!(unseal_stats.vde_calls, units);
+        assert_eq!(unseal_stats.bytes_written, seal_stats.bytes_written);
+
+        // 不使用缓存时封装结果相同，长程依赖全部从文件读出
+        copy_and_pad(origin, sealed, &params).unwrap();
+        let (uncached, _, uncached_stats) = seal(&params, sealed, &vde_key, &replica, &mut BlockCache::disabled(), &Progress::none()).unwrap();
+        assert_eq!(uncached, commitments);
+        assert_eq!(uncached_stats.cache_hits, 0);
+        assert_eq!(uncached_stats.cache_misses, seal_stats.cache_hits + seal_stats.cache_misses);
+        assert_eq!(uncached_stats.bytes_read, seal_stats.bytes_read + seal_stats.cache_hits * params.block_pl as u64);
+
+        for path in [origin, sealed] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
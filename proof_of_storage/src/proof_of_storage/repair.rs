@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Write, Seek, SeekFrom};
+
+use super::common::{com_units, to_units, blake3_hash};
+use super::error::{PosError, Result};
+use super::merkle_tree::generate_merkle_tree_from_data;
+use super::params::PosPara;
+use super::prover::seal_block;
+use super::sector::{data_offset, read_header, write_header, read_origin_leaves, read_sealed_leaves, write_sealed_leaves};
+use super::stream::{pad_units, read_sealed_block, unseal_one};
+
+pub fn reseal_block(sector: &str, idx: usize, original_block: &Vec<u8>) -> Result<Vec<usize>> {
+    //! 用原始数据重新封装扇区文件 sector 中损坏的第 idx 个二级数据块，返回重新写回的二级数据块编号
+    //!
+    //! original_block: 原始文件中第 idx 个二级数据块的数据，至多 block_l 字节，不足部分补 0，pad 后须与 comm_d 的叶子一致。
+    //! 第 idx 个二级数据块没有损坏时不写回，返回空列表
+    //!
+    //! 之后的二级数据块以前一个封装后二级数据块的哈希值为链接哈希并导出长程依赖。输入有变化的二级数据块先按修复后的数据
+    //! 解封装，与 comm_d 的叶子一致则不必重新封装；否则说明它是依据损坏的数据封装的（如封装途中磁盘出错），
+    //! 按修复前的数据解封装后重新封装并写回，最后更新 comm_r 的叶子及文件头部的 comm_r
+    let mut file = OpenOptions::new()
+    .read(true)
+    .write(true)
+    .open(sector)?;
+    let mut header = read_header(&mut file)?;
+    header.check_sealed()?;
+    let params = &header.params;
+    let block_cnt = params.block_count();
+    if idx >= block_cnt {
+        return Err(PosError::InvalidParams(format!("block index {} out of range, block count is {}", idx, block_cnt)));
+    }
+    if original_block.len() > params.block_l {
+        return Err(PosError::InvalidParams(format!("original block has {} bytes, expected at most {}", original_block.len(), params.block_l)));
+    }
+    let (vde_key, iv) = (header.vde_key()?, header.iv());
+    let origin_leaves = read_origin_leaves(&mut file, params)?;
+
+    let mut cur_block = {
+        let mut buf = original_block.clone();
+        buf.resize(params.block_l, 0);
+        pad_units(&buf, params.unit_l)
+    };
+    if blake3_hash(&com_units(&cur_block)) != origin_leaves[idx] {
+        return Err(PosError::InvalidParams(format!("original block {} does not match comm_d", idx)));
+    }
+
+    // 被重新写回的二级数据块修复前的内容
+    let mut old_blocks = HashMap::new();
+    let (chain_id, depend_idxs) = chain_inputs(&mut file, params, idx, &iv, None)?;
+    let depend_blocks = load_blocks(&mut file, params, &depend_idxs, None)?;
+    seal_block(params, &mut cur_block, &depend_blocks, &chain_id, &vde_key, None);
+    let old_block = read_sealed_block(&mut file, params, idx)?;
+    if com_units(&cur_block) == old_block {
+        return Ok(vec![]);
+    }
+    write_block(&mut file, params, idx, &com_units(&cur_block))?;
+    old_blocks.insert(idx, old_block);
+    let mut resealed = vec![idx];
+
+    for idx2 in idx + 1..block_cnt {
+        let (chain_id, depend_idxs) = chain_inputs(&mut file, params, idx2, &iv, None)?;
+        // 前一个二级数据块及长程依赖都没有重新写回，输入不变
+        if !old_blocks.contains_key(&(idx2 - 1)) && depend_idxs.iter().all(|i| !old_blocks.contains_key(i)) {
+            continue;
+        }
+
+        let block = read_sealed_block(&mut file, params, idx2)?;
+        let depend_blocks = load_blocks(&mut file, params, &depend_idxs, None)?;
+        let unsealed = unseal_one(params, idx2, &block, &chain_id, depend_blocks.clone(), &vde_key, &iv)?;
+        if blake3_hash(&com_units(&unsealed[0])) == origin_leaves[idx2] {
+            continue;
+        }
+
+        let (old_chain_id, old_depend_idxs) = chain_inputs(&mut file, params, idx2, &iv, Some(&old_blocks))?;
+        let old_depend_blocks = load_blocks(&mut file, params, &old_depend_idxs, Some(&old_blocks))?;
+        let mut cur_block = unseal_one(params, idx2, &block, &old_chain_id, old_depend_blocks, &vde_key, &iv)?.remove(0);
+        if blake3_hash(&com_units(&cur_block)) != origin_leaves[idx2] {
+            return Err(PosError::CorruptData(format!("block {} is damaged as well, reseal it with its original data", idx2)));
+        }
+        seal_block(params, &mut cur_block, &depend_blocks, &chain_id, &vde_key, None);
+        write_block(&mut file, params, idx2, &com_units(&cur_block))?;
+        old_blocks.insert(idx2, block);
+        resealed.push(idx2);
+    }
+
+    // 封装完成后才损坏的二级数据块写回后哈希值与 comm_r 的叶子相同；封装途中损坏的（包括最后一个二级数据块），
+    // 叶子及 comm_r 是依据损坏的数据计算的，需要更新
+    let mut blocks_id = read_sealed_leaves(&mut file, params)?;
+    let mut leaves_changed = false;
+    for &idx2 in &resealed {
+        let block_id = blake3_hash(&read_sealed_block(&mut file, params, idx2)?);
+        if block_id != blocks_id[idx2] {
+            blocks_id[idx2] = block_id;
+            leaves_changed = true;
+        }
+    }
+    if leaves_changed {
+        let (_, _, comm_r) = generate_merkle_tree_from_data(&blocks_id)?;
+        write_sealed_leaves(&mut file, params, &blocks_id)?;
+        header.comm_r = comm_r;
+        write_header(&mut file, &header)?;
+    }
+    file.flush()?;
+    Ok(resealed)
+}
+
+fn chain_inputs(file: &mut File, params: &PosPara, idx2: usize, iv: &Vec<u8>, old_blocks: Option<&HashMap<usize, Vec<u8>>>) -> Result<(Vec<u8>, Vec<usize>)> {
+    //! 第 idx2 个二级数据块的链接哈希及长程依赖的编号；old_blocks 不为 None 时按修复前的数据导出
+    if idx2 == 0 {
+        return Ok((iv.clone(), vec![]));
+    }
+    let before_block = load_block(file, params, idx2 - 1, old_blocks)?;
+    let before_block_id = blake3_hash(&before_block);
+    let depend_idxs = params.long_depend.indices(params.block_count(), idx2, &before_block_id);
+    Ok((before_block_id, depend_idxs))
+}
+
+fn load_blocks(file: &mut File, params: &PosPara, idxs: &Vec<usize>, old_blocks: Option<&HashMap<usize, Vec<u8>>>) -> Result<Vec<Vec<Vec<u8>>>> {
+    let mut res = vec![];
+    for &i in idxs {
+        res.push(to_units(&load_block(file, params, i, old_blocks)?, params.unit_pl));
+    }
+    Ok(res)
+}
+
+fn load_block(file: &mut File, params: &PosPara, idx2: usize, old_blocks: Option<&HashMap<usize, Vec<u8>>>) -> Result<Vec<u8>> {
+    match old_blocks.and_then(|blocks| blocks.get(&idx2)) {
+        Some(block) => Ok(block.clone()),
+        None => read_sealed_block(file, params, idx2),
+    }
+}
+
+fn write_block(file: &mut File, params: &PosPara, idx2: usize, block: &Vec<u8>) -> Result<()> {
+    file.seek(SeekFrom::Start(data_offset(idx2 * params.block_pl) as u64))?;
+    file.write_all(block)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use std::io::Cursor;
+    use crate::proof_of_storage::api::Unsealer;
+    use crate::proof_of_storage::cache::BlockCache;
+    use crate::proof_of_storage::prover::prepare_params;
+    use crate::proof_of_storage::replica::ReplicaId;
+    use crate::proof_of_storage::progress::Progress;
+    use crate::proof_of_storage::stream::{pad_stream, seal_blocks, finish_seal};
+    use crate::proof_of_storage::test_util::{test_params, temp_path, seal_sector};
+
+    #[test]
+    fn test_reseal_block() {
+        let params = test_params();
+        let path = &temp_path("pos_test_reseal_block");
+        let data: Vec<u8> = (0..1900).map(|i| (i * 7 % 256) as u8).collect();
+        let original = |idx2: usize| data[usize::min(idx2 * params.block_l, data.len())..usize::min((idx2 + 1) * params.block_l, data.len())].to_vec();
+
+        seal_sector(&params, &data, 0, path);
+        let expected = fs::read(path).unwrap();
+
+        // 封装完成后第 3 个二级数据块损坏，只需重新封装它
+        let mut damaged = expected.clone();
+        damaged[data_offset(3 * params.block_pl) + 10] ^= 0xff;
+        fs::write(path, &damaged).unwrap();
+        assert!(Unsealer::open(path).unwrap().unseal_blocks(0, 8).is_err());
+        assert_eq!(reseal_block(path, 3, &original(3)).unwrap(), vec![3]);
+        assert_eq!(fs::read(path).unwrap(), expected);
+        // 没有损坏时不写回，最后一个二级数据块只有部分原始数据
+        assert!(reseal_block(path, 3, &original(3)).unwrap().is_empty());
+        assert!(reseal_block(path, 7, &original(7)).unwrap().is_empty());
+
+        let mut wrong = original(3);
+        wrong[0] ^= 1;
+        assert!(matches!(reseal_block(path, 3, &wrong), Err(PosError::InvalidParams(_))));
+        assert!(matches!(reseal_block(path, 8, &vec![]), Err(PosError::InvalidParams(_))));
+
+        // 封装途中第 2 个二级数据块损坏，之后的二级数据块依据损坏的数据封装，需要一并重新封装
+        let vde_key = prepare_params(params.unit_pl).unwrap();
+        let mut sealed = Cursor::new(vec![]);
+        let (data_len, comm_d) = pad_stream(&mut data.as_slice(), &mut sealed, &params, &Progress::none()).unwrap();
+        let replica = ReplicaId::new([1u8; 32], 0, [2u8; 32], comm_d);
+        let mut blocks_id = vec![];
+        let stop = |idx2: usize, _: &Vec<Vec<u8>>| if idx2 == 2 { Err(PosError::Cancelled) } else { Ok(()) };
+        assert!(seal_blocks(&mut sealed, &params, &vde_key, &replica.iv(), &mut blocks_id, &mut BlockCache::disabled(), stop).is_err());
+        sealed.get_mut()[data_offset(2 * params.block_pl) + 10] ^= 0xff;
+        blocks_id[2] = blake3_hash(&read_sealed_block(&mut sealed, &params, 2).unwrap());
+        seal_blocks(&mut sealed, &params, &vde_key, &replica.iv(), &mut blocks_id, &mut BlockCache::disabled(), |_, _| Ok(())).unwrap();
+        finish_seal(&mut sealed, &params, &vde_key, &replica, data_len, &blocks_id).unwrap();
+        fs::write(path, sealed.get_ref()).unwrap();
+
+        assert_eq!(reseal_block(path, 2, &original(2)).unwrap(), (2..8).collect::<Vec<usize>>());
+        assert_eq!(fs::read(path).unwrap(), expected);
+
+        // 封装途中最后一个二级数据块损坏，没有之后的二级数据块，comm_r 的叶子及文件头部也需要更新
+        let mut sealed = Cursor::new(vec![]);
+        pad_stream(&mut data.as_slice(), &mut sealed, &params, &Progress::none()).unwrap();
+        let mut blocks_id = vec![];
+        let stop = |idx2: usize, _: &Vec<Vec<u8>>| if idx2 == 7 { Err(PosError::Cancelled) } else { Ok(()) };
+        assert!(seal_blocks(&mut sealed, &params, &vde_key, &replica.iv(), &mut blocks_id, &mut BlockCache::disabled(), stop).is_err());
+        sealed.get_mut()[data_offset(7 * params.block_pl) + 10] ^= 0xff;
+        blocks_id[7] = blake3_hash(&read_sealed_block(&mut sealed, &params, 7).unwrap());
+        finish_seal(&mut sealed, &params, &vde_key, &replica, data_len, &blocks_id).unwrap();
+        fs::write(path, sealed.get_ref()).unwrap();
+
+        assert_eq!(reseal_block(path, 7, &original(7)).unwrap(), vec![7]);
+        assert_eq!(fs::read(path).unwrap(), expected);
+        fs::remove_file(path).unwrap();
+    }
+}
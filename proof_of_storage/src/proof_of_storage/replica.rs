@@ -0,0 +1,60 @@
+use blake3;
+
+use serde::{Serialize, Deserialize};
+
+/// 计算副本编号时使用的域分隔前缀
+const REPLICA_ID_TAG: &[u8] = b"postorage/replica-id/v1";
+
+/// 副本编号：绑定证明者、扇区编号、验证者给出的 ticket 以及原始数据（pad 后）的 merkle root
+///
+/// 封装时以 id() 作为第 0 个二级数据块的链式 iv，不同证明者或扇区无法共用同一份封装数据
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReplicaId {
+    pub prover_id: [u8; 32],
+    pub sector_num: u64,
+    pub ticket: [u8; 32],
+    pub comm_d: [u8; 32],
+}
+
+impl ReplicaId {
+    pub fn new(prover_id: [u8; 32], sector_num: u64, ticket: [u8; 32], comm_d: [u8; 32]) -> ReplicaId {
+        ReplicaId { prover_id, sector_num, ticket, comm_d }
+    }
+
+    pub fn id(&self) -> [u8; 32] {
+        //! blake3(tag || prover_id || sector_num || ticket || comm_d)，验证者可独立重新计算
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(REPLICA_ID_TAG);
+        hasher.update(&self.prover_id);
+        hasher.update(&self.sector_num.to_le_bytes());
+        hasher.update(&self.ticket);
+        hasher.update(&self.comm_d);
+        *hasher.finalize().as_bytes()
+    }
+
+    pub fn iv(&self) -> Vec<u8> {
+        //! 封装使用的链式 iv
+        self.id().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_replica_id() {
+        let replica = ReplicaId::new([1u8; 32], 7, [2u8; 32], [3u8; 32]);
+        assert_eq!(replica.id(), ReplicaId::new([1u8; 32], 7, [2u8; 32], [3u8; 32]).id());
+
+        let others = [
+            ReplicaId { prover_id: [4u8; 32], ..replica },
+            ReplicaId { sector_num: 8, ..replica },
+            ReplicaId { ticket: [4u8; 32], ..replica },
+            ReplicaId { comm_d: [4u8; 32], ..replica },
+        ];
+        for other in others {
+            assert_ne!(other.id(), replica.id());
+        }
+    }
+}
@@ -0,0 +1,305 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rug::Integer;
+use threadpool::ThreadPool;
+
+use super::cache::{BlockCache, DEFAULT_CACHE_BYTES};
+use super::error::{PosError, Result};
+use super::params::PosPara;
+use super::progress::{CancelToken, Progress, ProgressEvent, ProgressObserver};
+use super::prover::{copy_and_pad_with_progress, prepare_params, seal};
+use super::replica::ReplicaId;
+use super::sector::Commitments;
+use super::stats::SealStats;
+
+/// 一个封装任务：以 ticket 封装原始文件 input，写入扇区 sector_num 的文件 output
+#[derive(Clone, Debug, PartialEq)]
+pub struct SealJob {
+    pub input: String,
+    pub output: String,
+    pub sector_num: u64,
+    pub ticket: [u8; 32],
+}
+
+/// 每个任务的资源限制
+///
+/// cache_bytes 只限制 BlockCache 缓存的封装后二级数据块，以下内存不计入：pad 时的一个二级数据块，封装时的当前二级数据块
+/// 及其未命中缓存的长程依赖（至多 1 + 长程依赖个数 个二级数据块），comm_d、comm_r 的叶子（每个二级数据块 64 字节），
+/// 以及通过 mmap 访问扇区文件所占的页缓存（由操作系统管理）
+///
+/// io_bytes_per_sec 限制 pad 与封装两个阶段读写原始文件及扇区文件中二级数据块的速度，含未命中缓存的长程依赖，
+/// 不含文件头部及叶子
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SealLimits {
+    // 缓存封装后二级数据块的内存上限（字节），见 BlockCache
+    pub cache_bytes: usize,
+    // pad 与封装阶段读写原始文件及扇区文件的速度上限（字节每秒），None 表示不限制
+    pub io_bytes_per_sec: Option<u64>,
+}
+
+impl Default for SealLimits {
+    fn default() -> Self {
+        SealLimits { cache_bytes: DEFAULT_CACHE_BYTES, io_bytes_per_sec: None }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Sealed(Commitments),
+    Failed(String),
+    // 开始前或封装途中被取消，扇区文件停留在中间状态
+    Cancelled,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct JobReport {
+    pub job: SealJob,
+    pub status: JobStatus,
+    pub stats: Option<SealStats>,
+}
+
+/// 证明者 prover_id 的封装队列：同一个扇区只能顺序封装，不同扇区由 threads 个线程同时封装
+///
+/// run 阻塞到队列中的任务全部结束；其间可在其他线程中用 reports 查看各扇区的状态，或用 cancel_token 取消
+///
+/// 多个线程可以同时调用 run，每个任务只由其中一次 run 执行
+pub struct SealScheduler {
+    params: PosPara,
+    prover_id: [u8; 32],
+    vde_key: Integer,
+    threads: usize,
+    limits: SealLimits,
+    reports: Arc<Mutex<Vec<JobReport>>>,
+    // 当前及下一次 run 使用的取消标记，被取消的 run 结束后换用新的标记
+    token: Mutex<CancelToken>,
+}
+
+impl SealScheduler {
+    pub fn new(params: PosPara, prover_id: [u8; 32], threads: usize, limits: SealLimits) -> Result<SealScheduler> {
+        params.validate()?;
+        if threads == 0 {
+            return Err(PosError::InvalidParams("threads must be greater than 0".to_string()));
+        }
+        if limits.io_bytes_per_sec == Some(0) {
+            return Err(PosError::InvalidParams("io_bytes_per_sec must be greater than 0".to_string()));
+        }
+        let vde_key = prepare_params(params.unit_pl)?;
+        Ok(SealScheduler { params, prover_id, vde_key, threads, limits, reports: Arc::new(Mutex::new(vec![])), token: Mutex::new(CancelToken::default()) })
+    }
+
+    pub fn submit(&self, job: SealJob) -> usize {
+        //! 加入队列，返回任务编号
+        let mut reports = self.reports.lock().unwrap();
+        reports.push(JobReport { job, status: JobStatus::Queued, stats: None });
+        reports.len() - 1
+    }
+
+    pub fn reports(&self) -> Vec<JobReport> {
+        self.reports.lock().unwrap().clone()
+    }
+
+    pub fn status(&self, job_id: usize) -> Option<JobStatus> {
+        self.reports.lock().unwrap().get(job_id).map(|report| report.status.clone())
+    }
+
+    pub fn cancel_token(&self) -> CancelToken {
+        //! 取消正在进行的 run：尚未开始的任务不再开始，正在封装的任务在当前二级数据块完成后停止；
+        //! 在 run 之前取消则下一次 run 的任务全部记为 Cancelled
+        //!
+        //! 被取消的 run 结束后换用新的标记，之后的 run 不受影响，需要重新调用 cancel_token 获取
+        self.token.lock().unwrap().clone()
+    }
+
+    pub fn run(&self) -> Vec<JobReport> {
+        //! 执行队列中全部 Queued 的任务，返回所有任务的报告
+        //!
+        //! 在收集任务的同一次加锁中将其标记为 Running，同时调用的 run 不会重复执行同一个任务
+        let token = self.cancel_token();
+        let claimed: Vec<usize> = self.reports.lock().unwrap().iter_mut().enumerate()
+        .filter(|(_, report)| report.status == JobStatus::Queued)
+        .map(|(job_id, report)| {
+            report.status = JobStatus::Running;
+            job_id
+        })
+        .collect();
+
+        let pool = ThreadPool::new(self.threads);
+        for &job_id in &claimed {
+            let (params, prover_id, vde_key, limits) = (self.params.clone(), self.prover_id, self.vde_key.clone(), self.limits);
+            let (reports, token) = (self.reports.clone(), token.clone());
+            pool.execute(move || {
+                let job = reports.lock().unwrap()[job_id].job.clone();
+                let res = if token.is_cancelled() {
+                    Err(PosError::Cancelled)
+                }
+                else {
+                    seal_job(&params, &prover_id, &vde_key, &job, &limits, token)
+                };
+                let mut reports = reports.lock().unwrap();
+                match res {
+                    Ok((commitments, stats)) => {
+                        reports[job_id].status = JobStatus::Sealed(commitments);
+                        reports[job_id].stats = Some(stats);
+                    },
+                    Err(PosError::Cancelled) => reports[job_id].status = JobStatus::Cancelled,
+                    Err(err) => reports[job_id].status = JobStatus::Failed(err.to_string()),
+                }
+            });
+        }
+        pool.join();
+
+        if token.is_cancelled() {
+            let mut current = self.token.lock().unwrap();
+            if current.is_cancelled() {
+                *current = CancelToken::default();
+            }
+        }
+
+        // 只处理本次 run 执行的任务，其他 run 执行中的任务仍为 Running
+        let mut reports = self.reports.lock().unwrap();
+        for &job_id in &claimed {
+            if reports[job_id].status == JobStatus::Running {
+                reports[job_id].status = JobStatus::Failed("seal worker panicked".to_string());
+            }
+        }
+        reports.clone()
+    }
+}
+
+fn seal_job(params: &PosPara, prover_id: &[u8; 32], vde_key: &Integer, job: &SealJob, limits: &SealLimits, token: CancelToken) -> Result<(Commitments, SealStats)> {
+    // pad 与封装两个阶段共用同一个限速器
+    let progress = match limits.io_bytes_per_sec {
+        Some(bytes_per_sec) => Progress::with_token(IoThrottle::new(bytes_per_sec), token),
+        None => Progress::from_token(token),
+    };
+    let comm_d = copy_and_pad_with_progress(&job.input, &job.output, params, &progress)?;
+    let replica = ReplicaId::new(*prover_id, job.sector_num, job.ticket, comm_d);
+    let (commitments, _, stats) = seal(params, &job.output, vde_key, &replica, &mut BlockCache::new(limits.cache_bytes), &progress)?;
+    Ok((commitments, stats))
+}
+
+/// 累计任务开始以来 pad 与封装阶段读写的字节数（ProgressEvent::Io），超过速度上限则等待
+struct IoThrottle {
+    bytes_per_sec: u64,
+    // (开始时间, 已读写的字节数)
+    state: Mutex<(Instant, u64)>,
+}
+
+impl IoThrottle {
+    fn new(bytes_per_sec: u64) -> IoThrottle {
+        IoThrottle { bytes_per_sec, state: Mutex::new((Instant::now(), 0)) }
+    }
+}
+
+impl ProgressObserver for IoThrottle {
+    fn on_event(&self, event: &ProgressEvent) {
+        if let ProgressEvent::Io { bytes, .. } = *event {
+            let (start, total) = {
+                let mut state = self.state.lock().unwrap();
+                state.1 += bytes;
+                *state
+            };
+            let expected = Duration::from_secs_f64(total as f64 / self.bytes_per_sec as f64);
+            let elapsed = start.elapsed();
+            if expected > elapsed {
+                thread::sleep(expected - elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs;
+    use crate::proof_of_storage::api::Sealer;
+    use crate::proof_of_storage::test_util::{test_params_with_blocks, temp_path};
+
+    #[test]
+    fn test_seal_scheduler() {
+        let params = test_params_with_blocks(4);
+        let origin = temp_path("pos_test_scheduler_origin");
+        fs::write(&origin, vec![9u8; 700]).unwrap();
+
+        let limits = SealLimits { cache_bytes: params.block_pl * 2, io_bytes_per_sec: Some(1 << 30) };
+        let scheduler = SealScheduler::new(params.clone(), [1u8; 32], 2, limits).unwrap();
+        for sector_num in 0..3 {
+            scheduler.submit(SealJob { input: origin.clone(), output: temp_path(&format!("pos_test_scheduler_sealed_{}", sector_num)), sector_num, ticket: [2u8; 32] });
+        }
+        let missing = scheduler.submit(SealJob { input: temp_path("pos_test_scheduler_missing"), output: temp_path("pos_test_scheduler_sealed_3"), sector_num: 3, ticket: [2u8; 32] });
+        assert_eq!(scheduler.status(missing), Some(JobStatus::Queued));
+
+        let reports = scheduler.run();
+        assert!(matches!(reports[missing].status, JobStatus::Failed(_)));
+        // 与单独封装得到的扇区相同
+        let sealer = Sealer::new(params.clone(), [1u8; 32]).unwrap();
+        let reference = temp_path("pos_test_scheduler_reference");
+        for (sector_num, report) in reports.iter().enumerate().take(3) {
+            let header = sealer.seal(&origin, &reference, sector_num as u64, &[2u8; 32]).unwrap();
+            assert_eq!(report.status, JobStatus::Sealed(header.commitments()));
+            assert_eq!(report.stats.as_ref().unwrap().bytes_written, (params.block_count() * params.block_pl) as u64);
+            assert_eq!(fs::read(&report.job.output).unwrap(), fs::read(&reference).unwrap());
+            fs::remove_file(&report.job.output).unwrap();
+        }
+
+        // 取消后排队的任务不再开始，已经结束的任务不再执行
+        let job = scheduler.submit(SealJob { input: origin.clone(), output: temp_path("pos_test_scheduler_sealed_4"), sector_num: 4, ticket: [2u8; 32] });
+        scheduler.cancel_token().cancel();
+        let reports = scheduler.run();
+        assert_eq!(reports[job].status, JobStatus::Cancelled);
+        assert!(matches!(reports[0].status, JobStatus::Sealed(_)));
+        // 被取消的 run 结束后换用新的标记，之后的 run 正常执行
+        let job = scheduler.submit(SealJob { input: origin.clone(), output: temp_path("pos_test_scheduler_sealed_5"), sector_num: 5, ticket: [2u8; 32] });
+        assert!(!scheduler.cancel_token().is_cancelled());
+        let reports = scheduler.run();
+        assert!(matches!(reports[job].status, JobStatus::Sealed(_)));
+        assert_eq!(reports[job - 1].status, JobStatus::Cancelled);
+        fs::remove_file(&reports[job].job.output).unwrap();
+
+        assert!(SealScheduler::new(params.clone(), [1u8; 32], 0, SealLimits::default()).is_err());
+        assert!(SealScheduler::new(params, [1u8; 32], 1, SealLimits { io_bytes_per_sec: Some(0), ..SealLimits::default() }).is_err());
+        fs::remove_file(&origin).unwrap();
+        fs::remove_file(&reference).unwrap();
+    }
+
+    #[test]
+    fn test_seal_scheduler_concurrent_run() {
+        let params = test_params_with_blocks(4);
+        let origin = temp_path("pos_test_scheduler_concurrent_origin");
+        fs::write(&origin, vec![7u8; 900]).unwrap();
+
+        // pad 与封装阶段都计入限速：每个任务读写 (900 + 4 * block_pl) + 2 * 4 * block_pl 字节以上
+        let bytes_per_sec = 20_000;
+        let limits = SealLimits { cache_bytes: 0, io_bytes_per_sec: Some(bytes_per_sec) };
+        let scheduler = SealScheduler::new(params.clone(), [1u8; 32], 1, limits).unwrap();
+        for sector_num in 0..2 {
+            scheduler.submit(SealJob { input: origin.clone(), output: temp_path(&format!("pos_test_scheduler_concurrent_{}", sector_num)), sector_num, ticket: [2u8; 32] });
+        }
+
+        // 两次 run 同时执行，每个任务只被其中一次 run 执行
+        let start = Instant::now();
+        let (first, second) = thread::scope(|scope| {
+            let first = scope.spawn(|| scheduler.run());
+            let second = scope.spawn(|| scheduler.run());
+            (first.join().unwrap(), second.join().unwrap())
+        });
+        let pad_bytes = 900 + params.block_count() * params.block_pl;
+        let mut min_elapsed = Duration::ZERO;
+        for report in scheduler.reports() {
+            assert!(matches!(report.status, JobStatus::Sealed(_)));
+            let stats = report.stats.as_ref().unwrap();
+            assert_eq!(stats.cache_hits, 0);
+            let job_bytes = pad_bytes as u64 + stats.bytes_read + stats.bytes_written;
+            min_elapsed = min_elapsed.max(Duration::from_secs_f64(job_bytes as f64 / bytes_per_sec as f64));
+            fs::remove_file(&report.job.output).unwrap();
+        }
+        assert!(start.elapsed() >= min_elapsed);
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 2);
+        fs::remove_file(&origin).unwrap();
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::str::FromStr;
+use rug::Integer;
+
+use serde::{Serialize, Deserialize};
+
+use super::error::{PosError, Result};
+use super::params::PosPara;
+use super::replica::ReplicaId;
+
+/// 封装文件头部的魔数
+pub const SECTOR_MAGIC: [u8; 8] = *b"POSECTOR";
+/// 封装文件格式版本
+pub const SECTOR_VERSION: u16 = 4;
+/// 文件头部预留长度，封装数据从该偏移开始存放
+pub const SECTOR_HEADER_LEN: usize = 4096;
+
+// magic (8) + version (2) + body len (4)
+const PREFIX_LEN: usize = 8 + 2 + 4;
+
+/// 封装时在同一遍计算得到的承诺：原始数据（pad 后）及封装数据的 merkle root
+///
+/// 叶子为各二级数据块的 blake3 哈希值
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Commitments {
+    pub comm_d: [u8; 32],
+    pub comm_r: [u8; 32],
+}
+
+/// 封装文件的元数据，解封装只需要该文件本身
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SectorHeader {
+    pub params: PosPara,
+    // vde 使用的素数（十进制）
+    pub prime: String,
+    // 与 comm_d 一起决定副本编号，见 ReplicaId
+    pub prover_id: [u8; 32],
+    pub sector_num: u64,
+    pub ticket: [u8; 32],
+    // 原始数据长度（字节）
+    pub data_len: u64,
+    // 原始数据（pad 后）及封装数据的 merkle root
+    pub comm_d: [u8; 32],
+    pub comm_r: [u8; 32],
+    // false 表示数据已被原地解封装
+    pub sealed: bool,
+}
+
+impl SectorHeader {
+    pub fn vde_key(&self) -> Result<Integer> {
+        Integer::from_str(&self.prime).map_err(|err| PosError::CorruptData(format!("bad prime in sector header: {}", err)))
+    }
+
+    pub fn commitments(&self) -> Commitments {
+        Commitments { comm_d: self.comm_d, comm_r: self.comm_r }
+    }
+
+    pub fn replica_id(&self) -> ReplicaId {
+        ReplicaId::new(self.prover_id, self.sector_num, self.ticket, self.comm_d)
+    }
+
+    pub fn iv(&self) -> Vec<u8> {
+        //! 封装使用的链式 iv，由副本编号导出
+        self.replica_id().iv()
+    }
+
+    pub fn check_params(&self, params: &PosPara) -> Result<()> {
+        //! 文件中记录的参数与调用者给出的参数不一致时返回错误
+        if self.params != *params {
+            return Err(PosError::InvalidParams(format!("sector was sealed with {:?}, got {:?}", self.params, params)));
+        }
+        Ok(())
+    }
+
+    pub fn check_sealed(&self) -> Result<()> {
+        if !self.sealed {
+            return Err(PosError::CorruptData("sector has already been unsealed in place".to_string()));
+        }
+        Ok(())
+    }
+}
+
+pub fn data_offset(begin_idx: usize) -> usize {
+    //! 封装数据中第 begin_idx 字节在文件中的位置
+    SECTOR_HEADER_LEN + begin_idx
+}
+
+pub fn leaves_offset(params: &PosPara) -> usize {
+    //! 封装数据之后依次存放各原始二级数据块（pad 后）的 blake3 哈希值，即 comm_d 的叶子
+    data_offset(params.block_count() * params.block_pl)
+}
+
+pub fn sealed_leaves_offset(params: &PosPara) -> usize {
+    //! comm_d 的叶子之后依次存放各封装后二级数据块的 blake3 哈希值，即 comm_r 的叶子
+    leaves_offset(params) + params.block_count() * 32
+}
+
+pub fn write_origin_leaves<F: Write + Seek>(file: &mut F, params: &PosPara, leaves: &Vec<Vec<u8>>) -> Result<()> {
+    write_leaves(file, params, leaves_offset(params), leaves)
+}
+
+pub fn read_origin_leaves<F: Read + Seek>(file: &mut F, params: &PosPara) -> Result<Vec<Vec<u8>>> {
+    read_leaves(file, params, leaves_offset(params), "comm_d")
+}
+
+pub fn write_sealed_leaves<F: Write + Seek>(file: &mut F, params: &PosPara, leaves: &Vec<Vec<u8>>) -> Result<()> {
+    write_leaves(file, params, sealed_leaves_offset(params), leaves)
+}
+
+pub fn read_sealed_leaves<F: Read + Seek>(file: &mut F, params: &PosPara) -> Result<Vec<Vec<u8>>> {
+    read_leaves(file, params, sealed_leaves_offset(params), "comm_r")
+}
+
+fn write_leaves<F: Write + Seek>(file: &mut F, params: &PosPara, offset: usize, leaves: &Vec<Vec<u8>>) -> Result<()> {
+    if leaves.len() != params.block_count() || leaves.iter().any(|leaf| leaf.len() != 32) {
+        return Err(PosError::InvalidParams(format!("expected {} leaves of 32 bytes", params.block_count())));
+    }
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.write_all(&leaves.concat())?;
+    Ok(())
+}
+
+fn read_leaves<F: Read + Seek>(file: &mut F, params: &PosPara, offset: usize, name: &str) -> Result<Vec<Vec<u8>>> {
+    let mut buf = vec![0u8; params.block_count() * 32];
+    file.seek(SeekFrom::Start(offset as u64))?;
+    file.read_exact(&mut buf).map_err(|_| PosError::CorruptData(format!("sector file is too short to hold the {} leaves", name)))?;
+    Ok(buf.chunks(32).map(|leaf| leaf.to_vec()).collect())
+}
+
+pub fn write_header<F: Write + Seek>(file: &mut F, header: &SectorHeader) -> Result<()> {
+    let body = bincode::serialize(header).map_err(|err| PosError::InvalidParams(err.to_string()))?;
+    if PREFIX_LEN + body.len() > SECTOR_HEADER_LEN {
+        return Err(PosError::InvalidParams(format!("sector header needs {} bytes, only {} reserved", PREFIX_LEN + body.len(), SECTOR_HEADER_LEN)));
+    }
+
+    let mut buf = Vec::with_capacity(SECTOR_HEADER_LEN);
+    buf.extend_from_slice(&SECTOR_MAGIC);
+    buf.extend_from_slice(&SECTOR_VERSION.to_le_bytes());
+    buf.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&body);
+    buf.resize(SECTOR_HEADER_LEN, 0);
+
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&buf)?;
+    Ok(())
+}
+
+pub fn read_header<F: Read + Seek>(file: &mut F) -> Result<SectorHeader> {
+    let mut buf = vec![0u8; SECTOR_HEADER_LEN];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut buf).map_err(|_| PosError::CorruptData("file is too short to hold a sector header".to_string()))?;
+
+    if buf[0..8] != SECTOR_MAGIC {
+        return Err(PosError::CorruptData("not a sealed sector file (bad magic)".to_string()));
+    }
+    let version = u16::from_le_bytes([buf[8], buf[9]]);
+    if version != SECTOR_VERSION {
+        return Err(PosError::CorruptData(format!("unsupported sector version {}, expected {}", version, SECTOR_VERSION)));
+    }
+    let body_len = u32::from_le_bytes([buf[10], buf[11], buf[12], buf[13]]) as usize;
+    if PREFIX_LEN + body_len > SECTOR_HEADER_LEN {
+        return Err(PosError::CorruptData(format!("sector header body length {} is too large", body_len)));
+    }
+    bincode::deserialize(&buf[PREFIX_LEN..PREFIX_LEN + body_len]).map_err(|err| PosError::CorruptData(format!("bad sector header: {}", err)))
+}
+
+pub fn read_header_from(path: &str) -> Result<SectorHeader> {
+    let mut file = OpenOptions::new()
+    .read(true)
+    .open(path)?;
+    read_header(&mut file)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proof_of_storage::common::blake3_hash;
+    use crate::proof_of_storage::depend::{DependCount, LongDependency, ShortDependency};
+    use crate::proof_of_storage::merkle_tree::generate_merkle_tree_from_data;
+    use crate::proof_of_storage::cache::BlockCache;
+    use crate::proof_of_storage::progress::Progress;
+    use crate::proof_of_storage::prover::{copy_and_pad, seal, unseal, copy_and_compress, prepare_params};
+    use crate::proof_of_storage::verifier::{create_random_file, batch_unseal_prepare, batch_unseal, batch_verify};
+    use crate::proof_of_storage::test_util::test_params;
+
+    fn random_depend_params() -> PosPara {
+        //! 共用的小参数，长程、短程依赖都随机选取
+        PosPara {
+            long_depend: LongDependency::Random(DependCount::Fixed(2)),
+            short_depend: ShortDependency::Random(2),
+            ..test_params()
+        }
+    }
+
+    #[test]
+    fn test_sector_round_trip() {
+        let params = random_depend_params();
+        let dir = std::env::temp_dir();
+        let origin_path = dir.join("pos_test_sector_origin");
+        let sealed_path = dir.join("pos_test_sector_sealed");
+        let unsealed_path = dir.join("pos_test_sector_unsealed");
+        let (origin_path, sealed_path, unsealed_path) = (origin_path.to_str().unwrap(), sealed_path.to_str().unwrap(), unsealed_path.to_str().unwrap());
+
+        create_random_file(origin_path, params.data_l).unwrap();
+        let comm_d = copy_and_pad(origin_path, sealed_path, &params).unwrap();
+        let vde_key = prepare_params(params.unit_pl).unwrap();
+        let replica = ReplicaId::new([1u8; 32], 3, [2u8; 32], comm_d);
+        let (commitments, ..) = seal(&params, sealed_path, &vde_key, &replica, &mut BlockCache::new(params.block_pl * 4), &Progress::none()).unwrap();
+
+        let header = read_header_from(sealed_path).unwrap();
+        assert_eq!(header.commitments(), commitments);
+
+        // 与事后重新读取文件计算得到的 merkle root 一致
+        let padded: Vec<u8> = std::fs::read(origin_path).unwrap().chunks(params.unit_l).flat_map(|unit| [unit, &[0u8]].concat()).collect();
+        let origin_leaves = padded.chunks(params.block_pl).map(|block| blake3_hash(&block.to_vec())).collect();
+        assert_eq!(generate_merkle_tree_from_data(&origin_leaves).unwrap().2, commitments.comm_d);
+        let sealed = std::fs::read(sealed_path).unwrap();
+        let sealed_leaves = sealed[SECTOR_HEADER_LEN..leaves_offset(&params)].chunks(params.block_pl).map(|block| blake3_hash(&block.to_vec())).collect();
+        assert_eq!(generate_merkle_tree_from_data(&sealed_leaves).unwrap().2, commitments.comm_r);
+        let mut sealed_file = std::fs::File::open(sealed_path).unwrap();
+        assert_eq!(read_origin_leaves(&mut sealed_file, &params).unwrap(), origin_leaves);
+        assert_eq!(read_sealed_leaves(&mut sealed_file, &params).unwrap(), sealed_leaves);
+
+        assert_eq!(header.params, params);
+        assert_eq!(header.vde_key().unwrap(), vde_key);
+        assert_eq!(header.replica_id(), replica);
+        assert_eq!(header.iv(), replica.iv());
+        assert_eq!(header.data_len, params.data_l as u64);
+        assert!(header.sealed);
+
+        // 参数不一致时拒绝解封装
+        let mut other = params.clone();
+        other.vde_rounds += 1;
+        assert!(matches!(unseal(&other, sealed_path, &Progress::none()), Err(PosError::InvalidParams(_))));
+        assert!(matches!(batch_unseal_prepare(sealed_path, 0, params.block_pl, &other, &mut BlockCache::disabled()), Err(PosError::InvalidParams(_))));
+
+        // 只依赖文件本身解封装部分数据块
+        let block_cnt = params.block_count();
+        let (blocks_idx, mut blocks, before_block_ids, depend_blocks) = batch_unseal_prepare(sealed_path, 0, block_cnt * params.block_pl, &params, &mut BlockCache::disabled()).unwrap();
+        // 使用缓存时读出的数据相同，前一个二级数据块总能命中
+        let mut cache = BlockCache::new(block_cnt * params.block_pl);
+        let cached = batch_unseal_prepare(sealed_path, 0, block_cnt * params.block_pl, &params, &mut cache).unwrap();
+        assert_eq!(cached, (blocks_idx.clone(), blocks.clone(), before_block_ids.clone(), depend_blocks.clone()));
+        assert!(cache.hits() >= block_cnt as u64 - 1);
+        let unsealed_blocks = batch_unseal(&params, &blocks_idx, &mut blocks, &before_block_ids, &depend_blocks, &header.vde_key().unwrap(), &header.iv(), &Progress::none()).unwrap();
+        for i in 0..blocks_idx.len() {
+            batch_verify(origin_path, blocks_idx[i], &unsealed_blocks[i], params.block_l, params.unit_l).unwrap();
+        }
+
+        unseal(&params, sealed_path, &Progress::none()).unwrap();
+        copy_and_compress(sealed_path, unsealed_path, params.data_l, params.unit_l, params.unit_pl).unwrap();
+        assert_eq!(std::fs::read(origin_path).unwrap(), std::fs::read(unsealed_path).unwrap());
+        assert!(matches!(unseal(&params, sealed_path, &Progress::none()), Err(PosError::CorruptData(_))));
+
+        for path in [origin_path, sealed_path, unsealed_path] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_bad_magic() {
+        let path = std::env::temp_dir().join("pos_test_sector_bad_magic");
+        std::fs::write(&path, vec![0u8; SECTOR_HEADER_LEN]).unwrap();
+        assert!(matches!(read_header_from(path.to_str().unwrap()), Err(PosError::CorruptData(_))));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
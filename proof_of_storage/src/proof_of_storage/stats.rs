@@ -0,0 +1,189 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+
+use serde::{Serialize, Deserialize};
+
+use super::error::{PosError, Result};
+
+/// 原地封装的统计：各部分耗时（秒）、读写二级数据块的字节数及 vde、哈希的调用次数
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SealStats {
+    // 封装阶段的总耗时，不含 copy_and_pad
+    pub elapsed: f32,
+    pub vde: f32,
+    pub file: f32,
+    pub depend: f32,
+    pub hash: f32,
+    pub block: f32,
+    pub modadd: f32,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub vde_calls: u64,
+    pub hash_calls: u64,
+    // 长程依赖命中、未命中 BlockCache 的次数
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+/// 原地解封装的统计，含义同 SealStats
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct UnsealStats {
+    pub elapsed: f32,
+    pub vde: f32,
+    pub file: f32,
+    pub depend: f32,
+    pub hash: f32,
+    pub block: f32,
+    pub modsub: f32,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    pub vde_calls: u64,
+    pub hash_calls: u64,
+}
+
+/// 可以写成一行 CSV 或一行 JSON 的统计记录
+pub trait StatsRecord: Serialize {
+    fn csv_header() -> Vec<String>;
+
+    fn csv_row(&self) -> Vec<String>;
+}
+
+impl StatsRecord for SealStats {
+    fn csv_header() -> Vec<String> {
+        ["elapsed", "vde", "file", "depend", "hash", "block", "modadd", "bytes_read", "bytes_written", "vde_calls", "hash_calls", "cache_hits", "cache_misses"]
+        .iter().map(|s| s.to_string()).collect()
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.elapsed.to_string(), self.vde.to_string(), self.file.to_string(), self.depend.to_string(), self.hash.to_string(), self.block.to_string(), self.modadd.to_string(),
+            self.bytes_read.to_string(), self.bytes_written.to_string(), self.vde_calls.to_string(), self.hash_calls.to_string(),
+            self.cache_hits.to_string(), self.cache_misses.to_string(),
+        ]
+    }
+}
+
+impl StatsRecord for UnsealStats {
+    fn csv_header() -> Vec<String> {
+        ["elapsed", "vde", "file", "depend", "hash", "block", "modsub", "bytes_read", "bytes_written", "vde_calls", "hash_calls"]
+        .iter().map(|s| s.to_string()).collect()
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.elapsed.to_string(), self.vde.to_string(), self.file.to_string(), self.depend.to_string(), self.hash.to_string(), self.block.to_string(), self.modsub.to_string(),
+            self.bytes_read.to_string(), self.bytes_written.to_string(), self.vde_calls.to_string(), self.hash_calls.to_string(),
+        ]
+    }
+}
+
+pub fn prefixed_header<T: StatsRecord>(prefix: &str) -> Vec<String> {
+    //! 组合多个统计记录时给列名加上前缀，如 seal_vde
+    T::csv_header().iter().map(|name| format!("{}_{}", prefix, name)).collect()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatsFormat {
+    // 第一行为列名
+    Csv,
+    // 每行一个 JSON 对象
+    JsonLines,
+}
+
+/// 将统计记录逐行写入 writer
+pub struct StatsWriter<W: Write> {
+    writer: W,
+    format: StatsFormat,
+    header_written: bool,
+}
+
+impl<W: Write> StatsWriter<W> {
+    pub fn new(writer: W, format: StatsFormat) -> StatsWriter<W> {
+        //! 写第一条 CSV 记录之前先写列名
+        StatsWriter { writer, format, header_written: false }
+    }
+
+    pub fn append(writer: W, format: StatsFormat) -> StatsWriter<W> {
+        //! 接在已有列名的 CSV 之后继续写，不再写列名
+        StatsWriter { writer, format, header_written: true }
+    }
+
+    pub fn write<T: StatsRecord>(&mut self, record: &T) -> Result<()> {
+        match self.format {
+            StatsFormat::Csv => {
+                if !self.header_written {
+                    writeln!(self.writer, "{}", csv_line(&T::csv_header()))?;
+                    self.header_written = true;
+                }
+                writeln!(self.writer, "{}", csv_line(&record.csv_row()))?;
+            },
+            StatsFormat::JsonLines => {
+                let line = serde_json::to_string(record).map_err(|err| PosError::InvalidParams(err.to_string()))?;
+                writeln!(self.writer, "{}", line)?;
+            },
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+pub fn open_stats_file(path: &str, format: StatsFormat) -> Result<StatsWriter<File>> {
+    //! 以追加方式打开统计文件，文件为空时先写列名
+    let file = OpenOptions::new()
+    .append(true)
+    .create(true)
+    .open(path)?;
+    if file.metadata()?.len() == 0 {
+        Ok(StatsWriter::new(file, format))
+    }
+    else {
+        Ok(StatsWriter::append(file, format))
+    }
+}
+
+fn csv_line(fields: &Vec<String>) -> String {
+    //! 含逗号、引号或换行的字段用引号括起，引号写两次
+    fields.iter().map(|field| {
+        if field.contains([',', '"', '\n']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        }
+        else {
+            field.clone()
+        }
+    }).collect::<Vec<String>>().join(",")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_stats_writer() {
+        let stats = SealStats { elapsed: 1.5, vde_calls: 8, hash_calls: 12, bytes_read: 1024, ..Default::default() };
+
+        let mut writer = StatsWriter::new(vec![], StatsFormat::Csv);
+        writer.write(&stats).unwrap();
+        writer.write(&stats).unwrap();
+        let csv = String::from_utf8(writer.into_inner()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "elapsed,vde,file,depend,hash,block,modadd,bytes_read,bytes_written,vde_calls,hash_calls,cache_hits,cache_misses");
+        assert_eq!(lines[1], "1.5,0,0,0,0,0,0,1024,0,8,12,0,0");
+
+        let mut writer = StatsWriter::append(vec![], StatsFormat::Csv);
+        writer.write(&UnsealStats::default()).unwrap();
+        assert_eq!(String::from_utf8(writer.into_inner()).unwrap().lines().count(), 1);
+
+        let mut writer = StatsWriter::new(vec![], StatsFormat::JsonLines);
+        writer.write(&stats).unwrap();
+        let json = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(serde_json::from_str::<SealStats>(json.trim_end()).unwrap(), stats);
+
+        assert_eq!(csv_line(&vec!["a".to_string(), "b,c".to_string(), "\"d\"".to_string()]), "a,\"b,c\",\"\"\"d\"\"\"");
+        assert_eq!(prefixed_header::<UnsealStats>("unseal")[6], "unseal_modsub");
+    }
+}
@@ -0,0 +1,360 @@
+use std::fs::OpenOptions;
+use std::io::{self, Read, Write, Seek, SeekFrom, BufWriter};
+
+use rug::Integer;
+
+use super::cache::{BlockCache, DEFAULT_CACHE_BYTES};
+use super::common::{read_file, to_units, com_units, blake3_hash};
+use super::error::{PosError, Result};
+use super::merkle_tree::generate_merkle_tree_from_data;
+use super::params::PosPara;
+use super::progress::{Phase, Progress};
+use super::prover::{prepare_params, seal_block};
+use super::replica::ReplicaId;
+use super::sector::{SectorHeader, SECTOR_HEADER_LEN, data_offset, read_header, read_header_from, write_header, write_origin_leaves, write_sealed_leaves};
+use super::mmap::SectorAccess;
+use super::verifier::{UnsealPool, batch_unseal, batch_unseal_prepare_with};
+
+/// unseal_parallel 每个线程每批解封装的二级数据块个数
+const UNSEAL_PARALLEL_BATCH: usize = 4;
+
+pub fn seal_stream<R: Read, S: Read + Write + Seek>(reader: &mut R, sealed: &mut S, params: &PosPara, prover_id: &[u8; 32], sector_num: u64, ticket: &[u8; 32]) -> Result<SectorHeader> {
+    //! 从 reader 读取至多 params.data_l 字节原始数据，pad 后封装写入 sealed，不需要暂存文件
+    //!
+    //! 不足 params.block_count() 个二级数据块的部分补 0，真实长度记录在文件头部
+    //!
+    //! 封装 iv 由副本编号导出，而副本编号依赖 comm_d：先将 pad 后的数据写入 sealed 并计算 comm_d，
+    //! 再逐个二级数据块回读、原地封装，长程依赖同样从 sealed 回读，因此 sealed 需要同时支持读写
+    seal_stream_with_progress(reader, sealed, params, prover_id, sector_num, ticket, &Progress::none())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn seal_stream_with_progress<R: Read, S: Read + Write + Seek>(reader: &mut R, sealed: &mut S, params: &PosPara, prover_id: &[u8; 32], sector_num: u64, ticket: &[u8; 32], progress: &Progress) -> Result<SectorHeader> {
+    //! 与 seal_stream 相同，pad 与封装的每个二级数据块完成后通知 progress，被取消时 sealed 停留在中间状态
+    params.validate()?;
+    let vde_key = prepare_params(params.unit_pl)?;
+    let (data_len, comm_d) = pad_stream(reader, sealed, params, progress)?;
+    let replica = ReplicaId::new(*prover_id, sector_num, *ticket, comm_d);
+
+    let block_cnt = params.block_count();
+    let seal_start = progress.start(Phase::Seal, block_cnt);
+    let mut blocks_id = vec![];
+    let mut cache = BlockCache::new(DEFAULT_CACHE_BYTES);
+    seal_blocks(sealed, params, &vde_key, &replica.iv(), &mut blocks_id, &mut cache, |idx2, _| progress.block_done(Phase::Seal, idx2, idx2 + 1, block_cnt))?;
+    progress.finish(Phase::Seal, seal_start);
+    finish_seal(sealed, params, &vde_key, &replica, data_len, &blocks_id)
+}
+
+pub fn pad_stream<R: Read, S: Write + Seek>(reader: &mut R, sealed: &mut S, params: &PosPara, progress: &Progress) -> Result<(u64, [u8; 32])> {
+    //! 第一遍：预留文件头部，将 pad 后的原始数据及 comm_d 的叶子写入 sealed，返回原始数据长度与 comm_d
+    sealed.seek(SeekFrom::Start(0))?;
+    sealed.write_all(&vec![0u8; SECTOR_HEADER_LEN])?;
+
+    let block_cnt = params.block_count();
+    let mut data_len = 0;
+    // 封装前各二级数据块（pad 后）的哈希值，用于计算 comm_d
+    let mut origin_blocks_id = Vec::with_capacity(block_cnt);
+    let pad_start = progress.start(Phase::Pad, block_cnt);
+    for idx2 in 0..block_cnt {
+        let mut buf = vec![0u8; params.block_l];
+        let len = usize::min(params.block_l, params.data_l - idx2 * params.block_l);
+        data_len += read_full(reader, &mut buf[..len])?;
+
+        let block = com_units(&pad_units(&buf, params.unit_l));
+        origin_blocks_id.push(blake3_hash(&block));
+        sealed.write_all(&block)?;
+        progress.block_done(Phase::Pad, idx2, idx2 + 1, block_cnt)?;
+    }
+
+    if reader.read(&mut [0u8; 1])? != 0 {
+        return Err(PosError::InvalidParams(format!("input is longer than {} bytes", params.data_l)));
+    }
+
+    // comm_d 的叶子存放在封装数据之后，复制证明的证明者由此生成 comm_d 的 merkle 路径
+    write_origin_leaves(sealed, params, &origin_blocks_id)?;
+    let (_, _, comm_d) = generate_merkle_tree_from_data(&origin_blocks_id)?;
+    progress.finish(Phase::Pad, pad_start);
+    Ok((data_len as u64, comm_d))
+}
+
+pub fn seal_blocks<S, F>(sealed: &mut S, params: &PosPara, vde_key: &Integer, iv: &Vec<u8>, blocks_id: &mut Vec<Vec<u8>>, cache: &mut BlockCache, mut after_block: F) -> Result<()>
+where
+    S: Read + Write + Seek,
+    F: FnMut(usize, &Vec<Vec<u8>>) -> Result<()>,
+{
+    //! 第二遍：blocks_id 为前 blocks_id.len() 个已封装二级数据块的哈希值，从下一个二级数据块开始逐个回读、原地封装
+    //!
+    //! 每封装并写回一个二级数据块后调用 after_block(idx2, blocks_id)；封装后的二级数据块放入 cache 供之后的长程依赖读取
+    for idx2 in blocks_id.len()..params.block_count() {
+        let mut cur_block = to_units(&read_sealed_block(sealed, params, idx2)?, params.unit_pl);
+        let (depend_blocks, chain_id) = {
+            if idx2 == 0 {
+                (vec![], iv.clone())
+            }
+            else {
+                (read_depend_blocks(sealed, params, idx2, &blocks_id[idx2 - 1], cache)?, blocks_id[idx2 - 1].clone())
+            }
+        };
+        seal_block(params, &mut cur_block, &depend_blocks, &chain_id, vde_key, None);
+
+        let sealed_block = com_units(&cur_block);
+        cache.insert(idx2, cur_block);
+        blocks_id.push(blake3_hash(&sealed_block));
+        sealed.seek(SeekFrom::Start(data_offset(idx2 * params.block_pl) as u64))?;
+        sealed.write_all(&sealed_block)?;
+        after_block(idx2, blocks_id)?;
+    }
+    Ok(())
+}
+
+pub fn finish_seal<S: Write + Seek>(sealed: &mut S, params: &PosPara, vde_key: &Integer, replica: &ReplicaId, data_len: u64, blocks_id: &Vec<Vec<u8>>) -> Result<SectorHeader> {
+    //! 全部二级数据块封装完成后计算 comm_r，写入文件头部，记录解封装所需的全部参数
+    //!
+    //! comm_r 的叶子存放在 comm_d 的叶子之后，证明者由此生成 merkle 路径，不必重新读取整个扇区
+    let (_, _, comm_r) = generate_merkle_tree_from_data(blocks_id)?;
+    write_sealed_leaves(sealed, params, blocks_id)?;
+    let header = SectorHeader {
+        params: params.clone(),
+        prime: vde_key.to_string(),
+        prover_id: replica.prover_id,
+        sector_num: replica.sector_num,
+        ticket: replica.ticket,
+        data_len,
+        comm_d: replica.comm_d,
+        comm_r,
+        sealed: true,
+    };
+    write_header(sealed, &header)?;
+    sealed.flush()?;
+    Ok(header)
+}
+
+pub fn unseal_stream<S: Read + Seek, W: Write>(sealed: &mut S, writer: &mut W, params: &PosPara) -> Result<()> {
+    //! 按顺序解封装 sealed 中的全部二级数据块，去掉 pad 后写入 writer，sealed 保持不变
+    unseal_stream_with_progress(sealed, writer, params, &Progress::none())
+}
+
+pub fn unseal_stream_with_progress<S: Read + Seek, W: Write>(sealed: &mut S, writer: &mut W, params: &PosPara, progress: &Progress) -> Result<()> {
+    //! 与 unseal_stream 相同，每解封装一个二级数据块通知 progress，被取消时 writer 中只有前面的部分数据
+    params.validate()?;
+    let header = read_header(sealed)?;
+    header.check_params(params)?;
+    header.check_sealed()?;
+    let vde_key = header.vde_key()?;
+
+    let block_cnt = params.block_count();
+    let data_len = header.data_len as usize;
+    if data_len > params.data_l {
+        return Err(PosError::CorruptData(format!("sector records {} bytes of data, params allow at most {}", data_len, params.data_l)));
+    }
+
+    let unseal_start = progress.start(Phase::Unseal, block_cnt);
+    let mut cache = BlockCache::new(DEFAULT_CACHE_BYTES);
+    let mut before_block_id = vec![];
+    for idx2 in 0..block_cnt {
+        let block = read_sealed_block(sealed, params, idx2)?;
+        let depend_blocks = read_depend_blocks(sealed, params, idx2, &before_block_id, &mut cache)?;
+        cache.insert(idx2, to_units(&block, params.unit_pl));
+        let unsealed_blocks = unseal_one(params, idx2, &block, &before_block_id, depend_blocks, &vde_key, &header.iv())?;
+
+        let data = strip_padding(params, idx2, &unsealed_blocks[0], data_len)?;
+        writer.write_all(&data)?;
+        before_block_id = blake3_hash(&block);
+        progress.block_done(Phase::Unseal, idx2, idx2 + 1, block_cnt)?;
+    }
+    writer.flush()?;
+    progress.finish(Phase::Unseal, unseal_start);
+    Ok(())
+}
+
+pub fn unseal_parallel(sealed: &str, out: &str, params: &PosPara, threads: usize) -> Result<()> {
+    //! 解封装扇区文件 sealed 中的全部二级数据块，去掉 pad 后按顺序写入 out，sealed 保持不变
+    //!
+    //! 每次读出 threads * UNSEAL_PARALLEL_BATCH 个二级数据块及其依赖，由 threads 个线程并行解封装后写出，
+    //! 内存占用与扇区大小无关；扇区文件只打开一次，各批共用同一个线程池
+    unseal_parallel_with_progress(sealed, out, params, threads, &Progress::none())
+}
+
+pub fn unseal_parallel_with_progress(sealed: &str, out: &str, params: &PosPara, threads: usize, progress: &Progress) -> Result<()> {
+    //! 与 unseal_parallel 相同，每写出一个二级数据块通知 progress，被取消时 out 中只有前面的部分数据
+    params.validate()?;
+    if threads == 0 {
+        return Err(PosError::InvalidParams("threads must be greater than 0".to_string()));
+    }
+    let header = read_header_from(sealed)?;
+    header.check_params(params)?;
+    header.check_sealed()?;
+    let (vde_key, iv) = (header.vde_key()?, header.iv());
+    let data_len = header.data_len as usize;
+    if data_len > params.data_l {
+        return Err(PosError::CorruptData(format!("sector records {} bytes of data, params allow at most {}", data_len, params.data_l)));
+    }
+
+    let mut writer = BufWriter::new(OpenOptions::new()
+    .write(true)
+    .create(true)
+    .truncate(true)
+    .open(out)?);
+
+    let block_cnt = params.block_count();
+    let batch = threads * UNSEAL_PARALLEL_BATCH;
+    let mut sector = SectorAccess::open(sealed, params, false)?;
+    let pool = UnsealPool::new(params, &vde_key, &iv, threads)?;
+    // 相邻两批共用前一个二级数据块及长程依赖
+    let mut cache = BlockCache::new(DEFAULT_CACHE_BYTES);
+    let unseal_start = progress.start(Phase::Unseal, block_cnt);
+    for begin in (0..block_cnt).step_by(batch) {
+        let end = usize::min(begin + batch, block_cnt);
+        let (blocks_idx, blocks, before_block_ids, depend_blocks) = batch_unseal_prepare_with(&mut sector, begin * params.block_pl, end * params.block_pl, params, &mut cache)?;
+        let unsealed_blocks = pool.unseal(blocks_idx, blocks, before_block_ids, depend_blocks, &progress.silent())?;
+        for (i, block) in unsealed_blocks.iter().enumerate() {
+            let idx2 = begin + i;
+            writer.write_all(&strip_padding(params, idx2, block, data_len)?)?;
+            progress.block_done(Phase::Unseal, idx2, idx2 + 1, block_cnt)?;
+        }
+    }
+    writer.flush()?;
+    progress.finish(Phase::Unseal, unseal_start);
+    Ok(())
+}
+
+pub fn pad_units(data: &Vec<u8>, unit_l: usize) -> Vec<Vec<u8>> {
+    //! 将原始数据按 unit_l 分成一级数据块，并在高位添加一个 0
+    let mut res = to_units(data, unit_l);
+    for unit in res.iter_mut() {
+        unit.push(0);
+    }
+    res
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    //! 读满 buf 或读到末尾为止，返回读到的字节数
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {},
+            Err(err) => return Err(PosError::Io(err)),
+        }
+    }
+    Ok(len)
+}
+
+pub fn strip_padding(params: &PosPara, idx2: usize, block: &Vec<Vec<u8>>, data_len: usize) -> Result<Vec<u8>> {
+    //! 去掉解封装后第 idx2 个二级数据块中一级数据块高位的 pad，以及超出 data_len 的补 0 部分
+    //!
+    //! pad 位或补 0 部分不为 0，说明解封装结果不正确
+    let begin = idx2 * params.block_l;
+    let mut res = Vec::with_capacity(params.block_l);
+    for (idx1, unit) in block.iter().enumerate() {
+        if unit[params.unit_l..].iter().any(|&b| b != 0) {
+            return Err(PosError::CorruptData(format!("unit {} of block {} has non-zero padding after unsealing", idx1, idx2)));
+        }
+        res.extend_from_slice(&unit[..params.unit_l]);
+    }
+
+    let len = usize::min(params.block_l, data_len.saturating_sub(begin));
+    if res[len..].iter().any(|&b| b != 0) {
+        return Err(PosError::CorruptData(format!("block {} has non-zero tail padding after unsealing", idx2)));
+    }
+    res.truncate(len);
+    Ok(res)
+}
+
+pub fn read_sealed_block<S: Read + Seek>(sealed: &mut S, params: &PosPara, idx2: usize) -> Result<Vec<u8>> {
+    read_file(sealed, data_offset(idx2 * params.block_pl), params.block_pl).map_err(|err| match err.kind() {
+        io::ErrorKind::UnexpectedEof => PosError::CorruptData(format!("sealed data ends within block {}", idx2)),
+        _ => PosError::Io(err),
+    })
+}
+
+pub fn read_depend_blocks<S: Read + Seek>(sealed: &mut S, params: &PosPara, idx2: usize, before_block_id: &Vec<u8>, cache: &mut BlockCache) -> Result<Vec<Vec<Vec<u8>>>> {
+    //! 读出第 idx2 个二级数据块长程依赖的封装后二级数据块，cache 中已有的不再从 sealed 读取
+    let block_cnt = params.block_count();
+    let mut res = vec![];
+    for i in params.long_depend.indices(block_cnt, idx2, before_block_id) {
+        res.push(cache.get_or_load(i, || Ok(to_units(&read_sealed_block(sealed, params, i)?, params.unit_pl)))?);
+    }
+    Ok(res)
+}
+
+pub fn unseal_one(params: &PosPara, idx2: usize, block: &Vec<u8>, before_block_id: &Vec<u8>, depend_blocks: Vec<Vec<Vec<u8>>>, vde_key: &Integer, iv: &Vec<u8>) -> Result<Vec<Vec<Vec<u8>>>> {
+    let mut blocks = vec![to_units(block, params.unit_pl)];
+    batch_unseal(params, &vec![idx2], &mut blocks, &vec![before_block_id.clone()], &vec![depend_blocks], vde_key, iv, &Progress::none())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Cursor;
+    use crate::proof_of_storage::api::Unsealer;
+    use crate::proof_of_storage::sector::sealed_leaves_offset;
+    use crate::proof_of_storage::test_util::test_params;
+
+    #[test]
+    fn test_stream_round_trip() {
+        let params = test_params();
+        let data: Vec<u8> = (0..params.data_l).map(|i| (i * 13 % 256) as u8).collect();
+
+        let mut sealed = Cursor::new(vec![]);
+        let header = seal_stream(&mut data.as_slice(), &mut sealed, &params, &[1u8; 32], 0, &[2u8; 32]).unwrap();
+        assert_eq!(sealed.get_ref().len(), sealed_leaves_offset(&params) + params.block_count() * 32);
+
+        let mut unsealed = vec![];
+        unseal_stream(&mut sealed, &mut unsealed, &params).unwrap();
+        assert_eq!(unsealed, data);
+
+        // 同一份数据，副本编号不同则封装结果不同
+        let other_header = seal_stream(&mut data.as_slice(), &mut Cursor::new(vec![]), &params, &[1u8; 32], 1, &[2u8; 32]).unwrap();
+        assert_eq!(other_header.comm_d, header.comm_d);
+        assert_ne!(other_header.comm_r, header.comm_r);
+        assert_ne!(other_header.iv(), header.iv());
+
+        // 流式封装的结果与文件接口兼容
+        let path = std::env::temp_dir().join("pos_test_stream_sealed");
+        std::fs::write(&path, sealed.get_ref()).unwrap();
+        let unsealer = Unsealer::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(unsealer.header(), &header);
+        assert_eq!(unsealer.unseal_blocks(2, 5).unwrap(), data[2 * params.block_l..5 * params.block_l]);
+
+        // 多线程分批解封装：1 个线程时分两批，3 个线程时一批
+        let out = std::env::temp_dir().join("pos_test_stream_unsealed");
+        let (path, out) = (path.to_str().unwrap(), out.to_str().unwrap());
+        for threads in [1, 3] {
+            unseal_parallel(path, out, &params, threads).unwrap();
+            assert_eq!(std::fs::read(out).unwrap(), data);
+        }
+        assert!(matches!(unseal_parallel(path, out, &params, 0), Err(PosError::InvalidParams(_))));
+        std::fs::remove_file(out).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let mut other = params.clone();
+        other.seal_rounds += 1;
+        assert!(matches!(unseal_stream(&mut sealed, &mut vec![], &other), Err(PosError::InvalidParams(_))));
+        sealed.get_mut().truncate(data_offset(params.block_pl * 3));
+        assert!(matches!(unseal_stream(&mut sealed, &mut vec![], &params), Err(PosError::CorruptData(_))));
+    }
+
+    #[test]
+    fn test_stream_input_len() {
+        // data_l 不是 block_l 的整数倍，输入也短于 data_l
+        let params = PosPara { data_l: 63 * 4 * 5 + 100, ..test_params() };
+        for len in [0, 1, 63 * 4, 63 * 4 * 3 + 70, params.data_l] {
+            let data: Vec<u8> = (0..len).map(|i| (i * 31 % 255 + 1) as u8).collect();
+            let mut sealed = Cursor::new(vec![]);
+            let header = seal_stream(&mut data.as_slice(), &mut sealed, &params, &[1u8; 32], 0, &[2u8; 32]).unwrap();
+            assert_eq!(header.data_len, len as u64);
+            assert_eq!(sealed.get_ref().len(), data_offset(6 * params.block_pl) + 2 * 6 * 32);
+
+            let mut unsealed = vec![];
+            unseal_stream(&mut sealed, &mut unsealed, &params).unwrap();
+            assert_eq!(unsealed, data);
+        }
+
+        let params = test_params();
+        let long = vec![1u8; params.data_l + 1];
+        let res = seal_stream(&mut long.as_slice(), &mut Cursor::new(vec![]), &params, &[1u8; 32], 0, &[2u8; 32]);
+        assert!(matches!(res, Err(PosError::InvalidParams(_))));
+    }
+}
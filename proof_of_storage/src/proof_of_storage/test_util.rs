@@ -0,0 +1,29 @@
+//! 单元测试共用的参数及扇区文件
+
+use std::fs;
+use std::io::Cursor;
+
+use super::params::PosPara;
+use super::sector::SectorHeader;
+use super::stream::seal_stream;
+
+pub(crate) fn test_params() -> PosPara {
+    //! 8 个二级数据块、每个二级数据块 4 个一级数据块的小参数
+    test_params_with_blocks(8)
+}
+
+pub(crate) fn test_params_with_blocks(block_cnt: usize) -> PosPara {
+    PosPara::builder().data_l(63 * 4 * block_cnt).block_l(63 * 4).big_block_l(63 * 4 * block_cnt).seal_rounds(1).vde_rounds(2).build().unwrap()
+}
+
+pub(crate) fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(name).to_str().unwrap().to_string()
+}
+
+pub(crate) fn seal_sector(params: &PosPara, data: &[u8], sector_num: u64, path: &str) -> SectorHeader {
+    //! 以 prover_id [1u8; 32]、ticket [2u8; 32] 流式封装 data，写入扇区文件 path，返回文件头部
+    let mut sealed = Cursor::new(vec![]);
+    let header = seal_stream(&mut &data[..], &mut sealed, params, &[1u8; 32], sector_num, &[2u8; 32]).unwrap();
+    fs::write(path, sealed.get_ref()).unwrap();
+    header
+}
@@ -1,78 +1,112 @@
-use std::collections::BTreeSet;
-use std::sync::{Arc, RwLock};
-use std::{fs::OpenOptions, io::Write};
+use std::collections::HashMap;
+use std::sync::{Arc, mpsc};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::fs::OpenOptions;
 
-use rand::Rng;
 use rug::Integer;
 use threadpool::ThreadPool;
 
 use crate::vde::rug_vde::vde_inv;
 
-use super::common::{read_file, to_units, modsub, blake3_hash};
-use super::depend::{short_depend_random, long_mode_random};
-use super::postorage::PosPara;
-use super::prover::{create_short_depend, create_long_depend};
-
+use super::cache::BlockCache;
+use super::common::{com_units, modsub, blake3_hash};
+#[cfg(any(test, feature = "experiments"))]
+use super::common::{read_file, to_units};
+use super::error::{PosError, Result};
+use super::mmap::SectorAccess;
+use super::params::PosPara;
+use super::progress::{Phase, Progress};
+use super::sector::read_header;
+
+#[cfg(any(test, feature = "experiments"))]
 pub fn create_random_file(path: &str, data_len: usize) -> std::io::Result<()> {
     //! 随机创建长度为 params.data_l 字节的文件
+    use std::io::Write;
+    use rand::Rng;
+
     let mut file = OpenOptions::new()
     .read(true)
     .write(true)
     .create(true)
     .truncate(true)
-    .open(path)
-    .unwrap();
+    .open(path)?;
 
     let mut rng = rand::thread_rng();
     for _ in 0..data_len {
         let buf: [u8; 1] = [rng.gen_range(0u8..=255u8)];
-        file.write_all(&buf).unwrap();
+        file.write_all(&buf)?;
     }
     Ok(())
 }
 
-pub fn create_challenges(n: usize, range: (usize, usize)) -> Vec<usize> {
-    //! 生成 count 个随机数，范围是 [left, right)
-    let mut rng = rand::thread_rng();
-    let mut set = BTreeSet::new();
+pub fn create_challenges(n: usize, range: (usize, usize)) -> Result<Vec<usize>> {
+    //! 生成 n 个互不相同的随机数并升序排列，范围是 [left, right)，n 超过范围大小时返回错误
+    let len = check_challenge_count(n, range)?;
+    let mut res: Vec<usize> = rand::seq::index::sample(&mut rand::thread_rng(), len, n).into_iter().map(|i| range.0 + i).collect();
+    res.sort_unstable();
+    Ok(res)
+}
 
-    while set.len() < n as usize {
-        let num = rng.gen_range(range.0 .. range.1);
-        set.insert(num);
+/// 由公开种子导出挑战时使用的域分隔前缀
+const CHALLENGE_TAG: &[u8] = b"postorage/challenge/v1";
+
+pub fn derive_challenges(seed: &[u8; 32], comm_r: &[u8; 32], replica_id: &[u8; 32], n: usize, range: (usize, usize)) -> Result<Vec<usize>> {
+    //! Fiat–Shamir：由公开种子（如区块哈希）、comm_r 与副本编号（ReplicaId::id）确定性地导出 n 个互不相同的挑战，范围是 [left, right)
+    //!
+    //! 第 i 个挑战取 blake3(tag || seed || comm_r || replica_id || i) 对剩余个数取模，再做部分 Fisher–Yates 交换，
+    //! 只需要 n 次哈希，没有拒绝采样；被交换过的位置记录在 map 中，内存与 n 成正比
+    let len = check_challenge_count(n, range)?;
+    let mut swapped: HashMap<usize, usize> = HashMap::new();
+    let mut res = Vec::with_capacity(n);
+    for i in 0..n {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(CHALLENGE_TAG);
+        hasher.update(seed);
+        hasher.update(comm_r);
+        hasher.update(replica_id);
+        hasher.update(&(i as u64).to_le_bytes());
+        let mut buf = [0u8; 16];
+        buf.copy_from_slice(&hasher.finalize().as_bytes()[..16]);
+        // 128 位随机数对不超过 usize 的数取模，偏差可以忽略
+        let j = i + (u128::from_le_bytes(buf) % (len - i) as u128) as usize;
+
+        let picked = *swapped.get(&j).unwrap_or(&j);
+        swapped.insert(j, *swapped.get(&i).unwrap_or(&i));
+        res.push(range.0 + picked);
     }
+    res.sort_unstable();
+    Ok(res)
+}
 
-    set.into_iter().collect()
+fn check_challenge_count(n: usize, range: (usize, usize)) -> Result<usize> {
+    if range.0 >= range.1 || n > range.1 - range.0 {
+        return Err(PosError::InvalidParams(format!("can't pick {} distinct challenges from [{}, {})", n, range.0, range.1)));
+    }
+    Ok(range.1 - range.0)
 }
 
+#[cfg(feature = "experiments")]
 pub fn single_unseal_prepare(sealed_path: &str, block_idx: usize, params: &PosPara) 
--> (Vec<Vec<u8>>, Vec<u8>, Vec<Vec<Vec<u8>>>) {
+-> Result<(Vec<Vec<u8>>, Vec<u8>, Vec<Vec<Vec<u8>>>)> {
     let mut sealed_file = OpenOptions::new()
     .read(true)
-    .open(sealed_path)
-    .unwrap();
+    .open(sealed_path)?;
 
-    // 从文件中读出二级数据块集合
-    let block = {
-        let buf = read_file(&mut sealed_file, block_idx * params.block_pl, params.block_pl);
-        let ans = to_units(&buf, params.unit_pl);
-        ans
-    };
-
-    let block_cnt = params.data_l / params.block_l;
+    let block_cnt = params.block_count();
+    if block_idx >= block_cnt {
+        return Err(PosError::InvalidParams(format!("block index {} out of range, block count is {}", block_idx, block_cnt)));
+    }
+    let header = read_header(&mut sealed_file)?;
+    header.check_params(params)?;
+    header.check_sealed()?;
+    let mut sector = SectorAccess::open(sealed_path, params, false)?;
 
-    let idxs_l = {
-        if params.mode_l != 0 {
-            create_long_depend(block_cnt, params.cnt_l, params.mode_l)
-        }
-        else {
-            vec![]
-        }
-    };
+    // 从文件中读出二级数据块集合
+    let block = sector.unit_vecs(block_idx)?;
 
     let before_block_id = {
         if block_idx != 0 {
-            let before_block = read_file(&mut sealed_file, (block_idx - 1) * params.block_pl, params.block_pl);
-            blake3_hash(&before_block)
+            blake3_hash(sector.block(block_idx - 1)?)
         }
         else {
             vec![]
@@ -80,47 +114,39 @@ pub fn single_unseal_prepare(sealed_path: &str, block_idx: usize, params: &PosPa
     };
 
     let depend_blocks = {
-        let mut res = vec![];
-        if params.mode_l == 0 {
-            let cur_idxs_l = {
-                if block_idx == 0 {
-                    vec![]
-                }
-                else {
-                    if params.cnt_l == 0 {
-                        long_mode_random(block_cnt, &before_block_id, block_idx, block_idx / 10 + 1)
-                    }
-                    else {
-                        long_mode_random(block_cnt, &before_block_id, block_idx, params.cnt_l)
-                    }
-                }
-            };
+        let cur_idxs_l = params.long_depend.indices(block_cnt, block_idx, &before_block_id);
 
-            for &idx in &cur_idxs_l {
-                let buf = read_file(&mut sealed_file, idx * params.block_pl, params.block_pl);
-                let ans = to_units(&buf, params.unit_pl);
-                res.push(ans);
-            }
-        }
-        else {
-            for &i in &idxs_l[block_idx] {
-                let buf = read_file(&mut sealed_file, i * params.block_pl, params.block_pl);  
-                let ans = to_units(&buf, params.unit_pl);
-                res.push(ans);
-            }
+        let mut res = vec![];
+        for &i in &cur_idxs_l {
+            res.push(sector.unit_vecs(i)?);
         }
         res
     };
 
-    (block, before_block_id, depend_blocks)
+    Ok((block, before_block_id, depend_blocks))
 }
 
-pub fn batch_unseal_prepare(sealed_path: &str, idx_begin: usize, idx_end: usize, params: &PosPara) 
--> (Vec<usize>, Vec<Vec<Vec<u8>>>, Vec<Vec<u8>>, Vec<Vec<Vec<Vec<u8>>>>) {
+pub fn batch_unseal_prepare(sealed_path: &str, idx_begin: usize, idx_end: usize, params: &PosPara, cache: &mut BlockCache) 
+-> Result<(Vec<usize>, Vec<Vec<Vec<u8>>>, Vec<Vec<u8>>, Vec<Vec<Vec<Vec<u8>>>>)> {
+    //! cache 中已有的封装后二级数据块不再从文件读取，读出的二级数据块放入 cache
     let mut sealed_file = OpenOptions::new()
     .read(true)
-    .open(sealed_path)
-    .unwrap();
+    .open(sealed_path)?;
+
+    let header = read_header(&mut sealed_file)?;
+    header.check_params(params)?;
+    header.check_sealed()?;
+    let mut sector = SectorAccess::open(sealed_path, params, false)?;
+    batch_unseal_prepare_with(&mut sector, idx_begin, idx_end, params, cache)
+}
+
+pub fn batch_unseal_prepare_with(sector: &mut SectorAccess, idx_begin: usize, idx_end: usize, params: &PosPara, cache: &mut BlockCache) 
+-> Result<(Vec<usize>, Vec<Vec<Vec<u8>>>, Vec<Vec<u8>>, Vec<Vec<Vec<Vec<u8>>>>)> {
+    //! 与 batch_unseal_prepare 相同，从已经打开并检查过文件头部的 sector 读取，逐批解封装同一个扇区时只需打开一次
+    let block_cnt = params.block_count();
+    if idx_begin > idx_end || idx_end > block_cnt * params.block_pl {
+        return Err(PosError::InvalidParams(format!("range [{}, {}) out of sealed data length {}", idx_begin, idx_end, block_cnt * params.block_pl)));
+    }
 
     let blocks_idx = {
         let mut res = vec![];
@@ -132,35 +158,24 @@ pub fn batch_unseal_prepare(sealed_path: &str, idx_begin: usize, idx_end: usize,
         res
     };
 
+    let mut read_block = |idx2: usize| cache.get_or_load(idx2, || sector.unit_vecs(idx2));
+
     // 从文件中读出二级数据块集合
     let blocks = {
         let mut res = vec![];
         for &idx2 in &blocks_idx {
-            let buf = read_file(&mut sealed_file, idx2 * params.block_pl, params.block_pl);
-            let ans = to_units(&buf, params.unit_pl);
-            res.push(ans);
+            res.push(read_block(idx2)?);
         }
         res
     };
 
-    let block_cnt = params.data_l / params.block_l;
-
-    let idxs_l = {
-        if params.mode_l != 0 {
-            create_long_depend(block_cnt, params.cnt_l, params.mode_l)
-        }
-        else {
-            vec![]
-        }
-    };
-
     let mut before_block_ids = vec![];
     let mut depend_blocks = vec![];
 
     for &idx2 in &blocks_idx {
         let single_before_block_id = {
             if idx2 != 0 {
-                let before_block = read_file(&mut sealed_file, (idx2 - 1) * params.block_pl, params.block_pl);
+                let before_block = com_units(&read_block(idx2 - 1)?);
                 blake3_hash(&before_block)
             }
             else {
@@ -169,34 +184,11 @@ pub fn batch_unseal_prepare(sealed_path: &str, idx_begin: usize, idx_end: usize,
         };
 
         let single_depend_blocks = {
+            let cur_idxs_l = params.long_depend.indices(block_cnt, idx2, &single_before_block_id);
+
             let mut res = vec![];
-            if params.mode_l == 0 {
-                let cur_idxs_l = {
-                    if idx2 == 0 {
-                        vec![]
-                    }
-                    else {
-                        if params.cnt_l == 0 {
-                            long_mode_random(block_cnt, &single_before_block_id, idx2, idx2 / 10 + 1)
-                        }
-                        else {
-                            long_mode_random(block_cnt, &single_before_block_id, idx2, params.cnt_l)
-                        }
-                    }
-                };
-
-                for &idx in &cur_idxs_l {
-                    let buf = read_file(&mut sealed_file, idx * params.block_pl, params.block_pl);
-                    let ans = to_units(&buf, params.unit_pl);
-                    res.push(ans);
-                }
-            }
-            else {
-                for &i in &idxs_l[idx2] {
-                    let buf = read_file(&mut sealed_file, i * params.block_pl, params.block_pl);  
-                    let ans = to_units(&buf, params.unit_pl);
-                    res.push(ans);
-                }
+            for &i in &cur_idxs_l {
+                res.push(read_block(i)?);
             }
             res
         };
@@ -204,301 +196,240 @@ pub fn batch_unseal_prepare(sealed_path: &str, idx_begin: usize, idx_end: usize,
         depend_blocks.push(single_depend_blocks);
     }
 
-    (blocks_idx, blocks, before_block_ids, depend_blocks)
+    Ok((blocks_idx, blocks, before_block_ids, depend_blocks))
 }
 
-pub fn batch_unseal_and_verify(params: &PosPara, origin_path: &str, blocks_idx: &Vec<usize>, blocks: &Vec<Vec<Vec<u8>>>, before_block_ids: &Vec<Vec<u8>>, depend_blocks: &Vec<Vec<Vec<Vec<u8>>>>, vde_key: &Integer, iv: &Vec<u8>) {
-    let idxs_s = {
-        if params.mode_s != 0 {
-            create_short_depend(params.data_l / params.block_l, params.cnt_s, params.mode_s)
-        }
-        else {
-            vec![]
-        }
-    };
-    
-    // 逐个解封装二级数据块
-    for i in 0..blocks.len() {
-        // 当前二级数据块编号
-        let idx2 = blocks_idx[i];
-        // 当前二级数据块内容
-        let mut cur_block = blocks[i].clone();
-        let unit_cnt = cur_block.len();
-
-        for _ in 0..params.seal_rounds {
-            for j in 0..unit_cnt {
-                let idx1 = unit_cnt - 1 - j;
-
-                let mut depend_data = {
-                    let mut res = vec![];
-                    for k in 0..depend_blocks[i].len() {
-                        res.append(&mut depend_blocks[i][k][idx1].clone());
-                    }
-                    if params.mode_s != 0 {
-                        for &idx in &idxs_s[idx1] {
-                            res.append(&mut cur_block[idx].clone());
-                        }
-                    }
-                    else {
-                        let ans = {
-                            if idx1 == 0 {
-                                short_depend_random(unit_cnt, &vec![], idx1, params.cnt_s)
-                            }
-                            else {
-                                short_depend_random(unit_cnt, &cur_block[idx1-1], idx1, params.cnt_s)
-                            }
-                        };
-
-                        for idx in ans {
-                            res.append(&mut cur_block[idx].clone());
-                        }
-                    }
-                    res
-                };
-
-                if idx1 == 0 {
-                    if idx2 == 0 {
-                        depend_data.append(&mut iv.clone());
-                    }
-                    else {
-                        depend_data.append(&mut before_block_ids[i].clone());
-                    }
-                }
+fn check_batch_len(blocks_idx: &Vec<usize>, blocks_len: usize, before_block_ids: &Vec<Vec<u8>>, depend_blocks: &Vec<Vec<Vec<Vec<u8>>>>) -> Result<()> {
+    //! 检查 batch_unseal_prepare 返回的各部分数据个数是否一致
+    if blocks_idx.len() != blocks_len || before_block_ids.len() != blocks_len || depend_blocks.len() != blocks_len {
+        return Err(PosError::InvalidParams(format!(
+            "batch length mismatch: {} indices, {} blocks, {} before block ids, {} depend blocks",
+            blocks_idx.len(), blocks_len, before_block_ids.len(), depend_blocks.len()
+        )));
+    }
+    Ok(())
+}
 
-                let depend_data_hash = blake3_hash(&depend_data);
-                let cur_unit = &cur_block[idx1].to_vec();
-                let vde_inv_res = vde_inv(&cur_unit, vde_key, params.vde_rounds, &params.vde_mode, params.unit_pl);
-                let new_unit = modsub(&vde_inv_res, &depend_data_hash, &vde_key);
-                cur_block[idx1] = new_unit;
-            }
+#[cfg(any(test, feature = "experiments"))]
+fn compare_unsealed_block(origin_block: &Vec<u8>, unseal_block: &Vec<Vec<u8>>, idx2: usize, unit_l: usize) -> Result<()> {
+    //! 将解封装得到的二级数据块（含 pad）与原始二级数据块逐个一级数据块比较
+    let origin_block = to_units(origin_block, unit_l);
+    if origin_block.len() != unseal_block.len() {
+        return Err(PosError::VerifyFailed { block: idx2, unit: origin_block.len().min(unseal_block.len()) });
+    }
+    for j in 0..origin_block.len() {
+        if unseal_block[j].len() <= unit_l || origin_block[j][..] != unseal_block[j][..unit_l] || unseal_block[j][unit_l..].iter().any(|&b| b != 0) {
+            return Err(PosError::VerifyFailed { block: idx2, unit: j });
         }
+    }
+    Ok(())
+}
+
+fn unseal_block(params: &PosPara, idx2: usize, cur_block: &mut Vec<Vec<u8>>, before_block_id: &Vec<u8>, depend_blocks: &Vec<Vec<Vec<u8>>>, vde_key: &Integer, iv: &Vec<u8>) {
+    //! 原地解封装第 idx2 个二级数据块，before_block_id 为前一个封装后二级数据块的哈希值（第 0 个二级数据块使用 iv）
+    let unit_cnt = cur_block.len();
+    for _ in 0..params.seal_rounds {
+        for j in 0..unit_cnt {
+            let idx1 = unit_cnt - 1 - j;
+
+            let mut depend_data = {
+                let mut res = vec![];
+                for depend_block in depend_blocks {
+                    res.extend_from_slice(&depend_block[idx1]);
+                }
+                let idxs_s = params.short_depend.indices(cur_block, idx1);
+                for idx in idxs_s {
+                    res.extend_from_slice(&cur_block[idx]);
+                }
+                res
+            };
 
-        let mut origin_file = OpenOptions::new()
-        .read(true)
-        .open(origin_path)
-        .unwrap();
-
-        let origin_block = read_file(&mut origin_file, idx2 * params.block_l, params.block_l);
-        let origin_block = to_units(&origin_block, params.unit_l);
-        for j in 0.. origin_block.len() {
-            let mut k_ori = 0;
-            let mut k_cur = 0;
-            let mut k = 0;
-            while k_ori < origin_block[j].len() {
-                if origin_block[j][k_ori] != cur_block[j][k_cur] {
-                    println!("{:?}", idx2);
-                    println!("{:?}", j);
-                    println!("{:?}", k_cur);
-                    println!("{:?}", k_ori);
+            if idx1 == 0 {
+                if idx2 == 0 {
+                    depend_data.extend_from_slice(iv);
                 }
-                assert_eq!(origin_block[j][k_ori], cur_block[j][k_cur]);
-                k_ori += 1;
-                k_cur += 1;
-                k += 1;
-
-                if k == params.unit_l {
-                    k_cur += 1;
-                    k = 0;
+                else {
+                    depend_data.extend_from_slice(before_block_id);
                 }
             }
+
+            let depend_data_hash = blake3_hash(&depend_data);
+            let vde_inv_res = vde_inv(&cur_block[idx1], vde_key, params.vde_rounds, params.vde_mode, params.unit_pl);
+            cur_block[idx1] = modsub(&vde_inv_res, &depend_data_hash, vde_key);
         }
     }
 }
 
-pub fn batch_unseal(params: &PosPara, blocks_idx: &Vec<usize>, blocks: &mut Vec<Vec<Vec<u8>>>, before_block_ids: &Vec<Vec<u8>>, depend_blocks: &Vec<Vec<Vec<Vec<u8>>>>, vde_key: &Integer, iv: &Vec<u8>) 
--> Vec<Vec<Vec<u8>>> {
-    let idxs_s = {
-        if params.mode_s != 0 {
-            create_short_depend(params.block_l / params.unit_l, params.cnt_s, params.mode_s)
-        }
-        else {
-            vec![]
-        }
-    };
-    
+#[allow(clippy::too_many_arguments)]
+pub fn batch_unseal(params: &PosPara, blocks_idx: &Vec<usize>, blocks: &mut Vec<Vec<Vec<u8>>>, before_block_ids: &Vec<Vec<u8>>, depend_blocks: &Vec<Vec<Vec<Vec<u8>>>>, vde_key: &Integer, iv: &Vec<u8>, progress: &Progress) 
+-> Result<Vec<Vec<Vec<u8>>>> {
+    check_batch_len(blocks_idx, blocks.len(), before_block_ids, depend_blocks)?;
+    let unseal_start = progress.start(Phase::Unseal, blocks.len());
+
     // 逐个解封装二级数据块
     for i in 0..blocks.len() {
-        // 当前二级数据块编号
         let idx2 = blocks_idx[i];
-        // 当前二级数据块内容
-        let mut cur_block = blocks[i].clone();
-        let unit_cnt = cur_block.len();
-
-        for _ in 0..params.seal_rounds {
-            for j in 0..unit_cnt {
-                let idx1 = unit_cnt - 1 - j;
-
-                let mut depend_data = {
-                    let mut res = vec![];
-                    for k in 0..depend_blocks[i].len() {
-                        res.append(&mut depend_blocks[i][k][idx1].clone());
-                    }
-                    if params.mode_s != 0 {
-                        for &idx in &idxs_s[idx1] {
-                            res.append(&mut cur_block[idx].clone());
-                        }
-                    }
-                    else {
-                        let ans = {
-                            if idx1 == 0 {
-                                short_depend_random(unit_cnt, &vec![], idx1, params.cnt_s)
-                            }
-                            else {
-                                short_depend_random(unit_cnt, &cur_block[idx1-1], idx1, params.cnt_s)
-                            }
-                        };
-
-                        for idx in ans {
-                            res.append(&mut cur_block[idx].clone());
-                        }
-                    }
-                    res
-                };
-
-                if idx1 == 0 {
-                    if idx2 == 0 {
-                        depend_data.append(&mut iv.clone());
-                    }
-                    else {
-                        depend_data.append(&mut before_block_ids[i].clone());
-                    }
-                }
+        unseal_block(params, idx2, &mut blocks[i], &before_block_ids[i], &depend_blocks[i], vde_key, iv);
+        progress.block_done(Phase::Unseal, idx2, i + 1, blocks_idx.len())?;
+    }
+    progress.finish(Phase::Unseal, unseal_start);
+    Ok(blocks.to_vec())
+}
 
-                let depend_data_hash = blake3_hash(&depend_data);
-                let cur_unit = &cur_block[idx1].to_vec();
-                let vde_inv_res = vde_inv(&cur_unit, vde_key, params.vde_rounds, &params.vde_mode, params.unit_pl);
-                let new_unit = modsub(&vde_inv_res, &depend_data_hash, &vde_key);
-                cur_block[idx1] = new_unit;
-            }
+#[allow(clippy::too_many_arguments)]
+pub fn batch_unseal_parallel(params: &PosPara, blocks_idx: Vec<usize>, blocks: Vec<Vec<Vec<u8>>>, before_block_ids: Vec<Vec<u8>>, depend_blocks: Vec<Vec<Vec<Vec<u8>>>>, vde_key: &Integer, iv: &Vec<u8>, parallel_num: usize, progress: &Progress) -> Result<Vec<Vec<Vec<u8>>>> {
+    //! 与 batch_unseal 相同，二级数据块由 parallel_num 个线程并行解封装，见 UnsealPool::unseal
+    UnsealPool::new(params, vde_key, iv, parallel_num)?.unseal(blocks_idx, blocks, before_block_ids, depend_blocks, progress)
+}
+
+/// 并行解封装的线程池，逐批解封装同一个扇区时只创建一次，参数、vde_key 与 iv 由各线程共享
+pub struct UnsealPool {
+    pool: ThreadPool,
+    params: Arc<PosPara>,
+    vde_key: Arc<Integer>,
+    iv: Arc<Vec<u8>>,
+}
+
+impl UnsealPool {
+    pub fn new(params: &PosPara, vde_key: &Integer, iv: &Vec<u8>, parallel_num: usize) -> Result<UnsealPool> {
+        if parallel_num == 0 {
+            return Err(PosError::InvalidParams("parallel_num must be greater than 0".to_string()));
         }
-        blocks[i] = cur_block;
+        Ok(UnsealPool {
+            pool: ThreadPool::new(parallel_num),
+            params: Arc::new(params.clone()),
+            vde_key: Arc::new(vde_key.clone()),
+            iv: Arc::new(iv.clone()),
+        })
     }
-    blocks.to_vec()
-}
 
-pub fn batch_unseal_parallel(params: &PosPara, blocks_idx: &Vec<usize>, blocks: &Vec<Vec<Vec<u8>>>, before_block_ids: &Vec<Vec<u8>>, depend_blocks: &Vec<Vec<Vec<Vec<u8>>>>, vde_key: &Integer, iv: &Vec<u8>, parallel_num: usize) -> Vec<Vec<Vec<u8>>> {
-    let idxs_s = {
-        if params.mode_s != 0 {
-            create_short_depend(params.data_l / params.block_l, params.cnt_s, params.mode_s)
+    pub fn unseal(&self, blocks_idx: Vec<usize>, blocks: Vec<Vec<Vec<u8>>>, before_block_ids: Vec<Vec<u8>>, depend_blocks: Vec<Vec<Vec<Vec<u8>>>>, progress: &Progress) -> Result<Vec<Vec<Vec<u8>>>> {
+        //! 与 batch_unseal 相同，每个二级数据块及其依赖移入解封装它的线程，结果按 blocks_idx 的顺序返回
+        //!
+        //! 被取消后尚未开始的二级数据块不再解封装，已开始的二级数据块完成后返回 Cancelled
+        check_batch_len(&blocks_idx, blocks.len(), &before_block_ids, &depend_blocks)?;
+        let total = blocks.len();
+        let unseal_start = progress.start(Phase::Unseal, total);
+        let done = Arc::new(AtomicUsize::new(0));
+        let (sender, receiver) = mpsc::channel();
+
+        let jobs = blocks_idx.into_iter().zip(blocks).zip(before_block_ids).zip(depend_blocks);
+        for (i, (((idx2, mut cur_block), before_block_id), depend_blocks)) in jobs.enumerate() {
+            let (params, vde_key, iv) = (self.params.clone(), self.vde_key.clone(), self.iv.clone());
+            let (progress, done, sender) = (progress.clone(), done.clone(), sender.clone());
+            self.pool.execute(move || {
+                if progress.check().is_err() {
+                    return;
+                }
+                unseal_block(&params, idx2, &mut cur_block, &before_block_id, &depend_blocks, &vde_key, &iv);
+                // 取消由 join 之后统一检查
+                let _ = progress.block_done(Phase::Unseal, idx2, done.fetch_add(1, Ordering::SeqCst) + 1, total);
+                let _ = sender.send((i, cur_block));
+            });
         }
-        else {
-            vec![]
+        drop(sender);
+        self.pool.join();
+        progress.check()?;
+
+        // 线程 panic 时其结果缺失
+        let mut res = vec![vec![]; total];
+        let mut received = 0;
+        for (i, block) in receiver {
+            res[i] = block;
+            received += 1;
         }
-    };
+        if received != total {
+            return Err(PosError::CorruptData(format!("{} unseal workers panicked", total - received)));
+        }
+        progress.finish(Phase::Unseal, unseal_start);
+        Ok(res)
+    }
+}
 
-    let pool = ThreadPool::new(parallel_num);
+#[cfg(any(test, feature = "experiments"))]
+pub fn batch_verify(origin_path: &str, idx2: usize, unseal_block: &Vec<Vec<u8>>, block_l: usize, unit_l: usize) -> Result<()> {
+    //! 验证解封装得到的第 idx2 个二级数据块与原始数据一致，不一致时返回 VerifyFailed
+    let mut origin_file = OpenOptions::new()
+    .read(true)
+    .open(origin_path)?;
 
-    let iv_arc = Arc::new(RwLock::new(iv.clone()));
-    let blocks_idx_arc = Arc::new(RwLock::new(blocks_idx.clone()));
-    let blocks_arc = Arc::new(RwLock::new(blocks.clone()));
-    let before_block_ids_arc = Arc::new(RwLock::new(before_block_ids.clone()));
-    let depend_blocks_arc = Arc::new(RwLock::new(depend_blocks.clone()));
-    let vde_key_arc = Arc::new(RwLock::new(vde_key.clone()));
-    let idxs_s_arc = Arc::new(RwLock::new(idxs_s.clone()));
+    let origin_block = read_file(&mut origin_file, idx2 * block_l, block_l)?;
+    compare_unsealed_block(&origin_block, unseal_block, idx2, unit_l)
+}
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::proof_of_storage::replica::ReplicaId;
+
+    #[test]
+    fn test_challenges() {
+        let replica = ReplicaId::new([1u8; 32], 0, [2u8; 32], [3u8; 32]).id();
+        let (seed, comm_r) = ([4u8; 32], [5u8; 32]);
+
+        let res = derive_challenges(&seed, &comm_r, &replica, 10, (5, 25)).unwrap();
+        assert_eq!(res, derive_challenges(&seed, &comm_r, &replica, 10, (5, 25)).unwrap());
+        assert!(res.windows(2).all(|w| w[0] < w[1]) && res[0] >= 5 && res[9] < 25);
+        assert_ne!(res, derive_challenges(&[6u8; 32], &comm_r, &replica, 10, (5, 25)).unwrap());
+        assert_ne!(res, derive_challenges(&seed, &comm_r, &ReplicaId::new([1u8; 32], 1, [2u8; 32], [3u8; 32]).id(), 10, (5, 25)).unwrap());
+
+        // 挑战全部编号时恰好是整个范围
+        assert_eq!(derive_challenges(&seed, &comm_r, &replica, 20, (5, 25)).unwrap(), (5..25).collect::<Vec<usize>>());
+        assert_eq!(create_challenges(20, (5, 25)).unwrap(), (5..25).collect::<Vec<usize>>());
+
+        assert!(matches!(derive_challenges(&seed, &comm_r, &replica, 21, (5, 25)), Err(PosError::InvalidParams(_))));
+        assert!(matches!(create_challenges(21, (5, 25)), Err(PosError::InvalidParams(_))));
+        assert!(create_challenges(1, (3, 3)).is_err());
+    }
 
-    let params_arc = Arc::new(RwLock::new(params.clone()));
+    #[test]
+    fn test_batch_verify_mismatch() {
+        let (block_l, unit_l) = (6, 3);
+        let path = std::env::temp_dir().join("pos_test_batch_verify_mismatch");
+        std::fs::write(&path, vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]).unwrap();
+        let path = path.to_str().unwrap();
 
-    // 逐个解封装一级数据块
-    for i in 0..blocks.len() {
+        let unseal_block = vec![vec![7, 8, 9, 0], vec![10, 11, 12, 0]];
+        assert!(batch_verify(path, 1, &unseal_block, block_l, unit_l).is_ok());
 
-        let iv_copy = iv_arc.clone();
-        let blocks_idx_copy = blocks_idx_arc.clone();
-        let blocks_copy = blocks_arc.clone();
-        let before_block_idxs_copy = before_block_ids_arc.clone();
-        let depend_blocks_copy = depend_blocks_arc.clone();
-        let vde_key_copy = vde_key_arc.clone();
-        let idxs_s_copy = idxs_s_arc.clone();
-
-        let params_copy = params_arc.clone();
-        
-        pool.execute(move || {
-            // 当前二级数据块编号
-            let idx2 = blocks_idx_copy.read().unwrap()[i];
-            // 当前二级数据块内容
-            let mut cur_block = blocks_copy.read().unwrap()[i].clone();
-            let unit_cnt = cur_block.len();
-
-            for _ in 0..params_copy.read().unwrap().seal_rounds {
-                for j in 0..unit_cnt {
-                    let idx1 = unit_cnt - 1 - j;
-
-                    let mut depend_data = {
-                        let mut res = vec![];
-                        for k in 0..depend_blocks_copy.read().unwrap()[i].len() {
-                            res.append(&mut depend_blocks_copy.read().unwrap()[i][k][idx1].clone());
-                        }
-                        if params_copy.read().unwrap().mode_s != 0 {
-                            for &idx in &idxs_s_copy.read().unwrap()[idx1] {
-                                res.append(&mut cur_block[idx].clone());
-                            }
-                        }
-                        else {
-                            let ans = {
-                                if idx1 == 0 {
-                                    short_depend_random(unit_cnt, &vec![], idx1, params_copy.read().unwrap().cnt_s)
-                                }
-                                else {
-                                    short_depend_random(unit_cnt, &cur_block[idx1-1], idx1, params_copy.read().unwrap().cnt_s)
-                                }
-                            };
-
-                            for idx in ans {
-                                res.append(&mut cur_block[idx].clone());
-                            }
-                        }
-                        res
-                    };
-                    if idx1 == 0 {
-                        if idx2 == 0 {
-                            depend_data.append(&mut iv_copy.read().unwrap().clone());
-                        }
-                        else {
-                            depend_data.append(&mut before_block_idxs_copy.read().unwrap()[i].clone());
-                        }
-                    }
-                    let depend_data_hash = blake3_hash(&depend_data);
-                    let cur_unit = &cur_block[idx1].to_vec();
-                    let vde_inv_res = vde_inv(&cur_unit, &vde_key_copy.read().unwrap(), params_copy.read().unwrap().vde_rounds, &params_copy.read().unwrap().vde_mode, params_copy.read().unwrap().unit_pl);
-                    let new_unit = modsub(&vde_inv_res, &depend_data_hash, &vde_key_copy.read().unwrap());
-                    cur_block[idx1] = new_unit;
-                }
-            }
-            blocks_copy.write().unwrap()[i] = cur_block;
-        });
-    }
-    pool.join();
+        let unseal_block = vec![vec![7, 8, 9, 0], vec![10, 0, 12, 0]];
+        assert!(matches!(batch_verify(path, 1, &unseal_block, block_l, unit_l), Err(PosError::VerifyFailed { block: 1, unit: 1 })));
 
-    blocks_arc.clone().read().unwrap().to_vec()
-}
+        // pad 位不为 0
+        let unseal_block = vec![vec![7, 8, 9, 1], vec![10, 11, 12, 0]];
+        assert!(matches!(batch_verify(path, 1, &unseal_block, block_l, unit_l), Err(PosError::VerifyFailed { block: 1, unit: 0 })));
+        std::fs::remove_file(path).unwrap();
+    }
 
-pub fn batch_verify(origin_path: &str, idx2: usize, unseal_block: &Vec<Vec<u8>>, block_l: usize, unit_l: usize) {
-    let mut origin_file = OpenOptions::new()
-    .read(true)
-    .open(origin_path)
-    .unwrap();
-
-    let origin_block = read_file(&mut origin_file, idx2 * block_l, block_l);
-    let origin_block = to_units(&origin_block, unit_l);
-    for j in 0.. origin_block.len() {
-        let mut k_ori = 0;
-        let mut k_cur = 0;
-        let mut k = 0;
-        while k_ori < origin_block[j].len() {
-            if origin_block[j][k_ori] != unseal_block[j][k_cur] {
-                println!("idx2: {:?}", idx2);
-                println!("j: {:?}", j);
-                println!("k_cur: {:?}", k_cur);
-                println!("k_ori: {:?}", k_ori);
-            }
-            assert_eq!(origin_block[j][k_ori], unseal_block[j][k_cur]);
-            k_ori += 1;
-            k_cur += 1;
-            k += 1;
-
-            if k == unit_l {
-                k_cur += 1;
-                k = 0;
+    #[test]
+    fn test_unseal_pool() {
+        use crate::proof_of_storage::progress::CancelToken;
+        use crate::proof_of_storage::stream::strip_padding;
+        use crate::proof_of_storage::test_util::{test_params, temp_path, seal_sector};
+
+        let params = test_params();
+        let path = &temp_path("pos_test_unseal_pool");
+        let data: Vec<u8> = (0..params.data_l).map(|i| (i * 11 % 256) as u8).collect();
+        let header = seal_sector(&params, &data, 0, path);
+        let (vde_key, iv) = (header.vde_key().unwrap(), header.iv());
+
+        // 同一个线程池与 SectorAccess 依次解封装各批，结果按二级数据块编号排列
+        let mut sector = SectorAccess::open(path, &params, false).unwrap();
+        let pool = UnsealPool::new(&params, &vde_key, &iv, 3).unwrap();
+        let mut cache = BlockCache::disabled();
+        let mut unsealed = vec![];
+        for (begin, end) in [(0, 5), (5, 8)] {
+            let (blocks_idx, blocks, before_block_ids, depend_blocks) = batch_unseal_prepare_with(&mut sector, begin * params.block_pl, end * params.block_pl, &params, &mut cache).unwrap();
+            for (i, block) in pool.unseal(blocks_idx, blocks, before_block_ids, depend_blocks, &Progress::none()).unwrap().iter().enumerate() {
+                unsealed.append(&mut strip_padding(&params, begin + i, block, data.len()).unwrap());
             }
         }
+        assert_eq!(unsealed, data);
+
+        let token = CancelToken::default();
+        token.cancel();
+        let (blocks_idx, blocks, before_block_ids, depend_blocks) = batch_unseal_prepare_with(&mut sector, 0, params.block_pl, &params, &mut cache).unwrap();
+        assert!(matches!(pool.unseal(blocks_idx, blocks, before_block_ids, depend_blocks, &Progress::from_token(token)), Err(PosError::Cancelled)));
+        assert!(matches!(UnsealPool::new(&params, &vde_key, &iv, 0), Err(PosError::InvalidParams(_))));
+        std::fs::remove_file(path).unwrap();
     }
-}
\ No newline at end of file
+}
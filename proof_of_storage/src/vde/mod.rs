@@ -1,4 +1,5 @@
 pub mod sloth;
+pub mod mode;
 pub mod rug_vde;
 pub mod compare_modpow;
 pub mod rug_sloth;
\ No newline at end of file
@@ -0,0 +1,97 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rug::Integer;
+use serde::{Serialize, Deserialize};
+
+use super::rug_sloth::{sloth, sloth_inv};
+
+/// 可验证延迟编码：encode 为慢方向（封装），decode 为快方向（解封装）
+pub trait Vde: Sync {
+    fn name(&self) -> &'static str;
+
+    fn encode(&self, x: &Integer, p: &Integer, t: usize) -> Integer;
+
+    fn decode(&self, y: &Integer, p: &Integer, t: usize) -> Integer;
+
+    /// 支持的一级数据块（pad 后）位数
+    fn unit_bits(&self) -> &'static [usize];
+
+    /// 慢方向的代价，以模乘次数计
+    fn slow_cost(&self, p: &Integer, t: usize) -> usize;
+}
+
+pub struct Sloth;
+
+impl Vde for Sloth {
+    fn name(&self) -> &'static str {
+        "sloth"
+    }
+
+    fn encode(&self, x: &Integer, p: &Integer, t: usize) -> Integer {
+        sloth(x, p, t)
+    }
+
+    fn decode(&self, y: &Integer, p: &Integer, t: usize) -> Integer {
+        sloth_inv(y, p, t)
+    }
+
+    fn unit_bits(&self) -> &'static [usize] {
+        &[512, 1024, 2048]
+    }
+
+    fn slow_cost(&self, p: &Integer, t: usize) -> usize {
+        // 每轮计算一次 (p + 1) / 4 次幂，约 log2(p) 次平方
+        t * p.significant_bits() as usize
+    }
+}
+
+/// PosPara 中记录的 vde 类型，新增编码时只需增加一个变体并在 vde() 中返回其实现
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum VdeMode {
+    #[default]
+    Sloth,
+}
+
+impl VdeMode {
+    pub const ALL: [VdeMode; 1] = [VdeMode::Sloth];
+
+    pub fn vde(&self) -> &'static dyn Vde {
+        match self {
+            VdeMode::Sloth => &Sloth,
+        }
+    }
+}
+
+impl fmt::Display for VdeMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.vde().name())
+    }
+}
+
+impl FromStr for VdeMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VdeMode::ALL.into_iter()
+        .find(|mode| mode.vde().name() == s)
+        .ok_or_else(|| format!("unknown vde mode {:?}, expected one of {:?}", s, VdeMode::ALL.map(|mode| mode.vde().name())))
+    }
+}
+
+#[test]
+fn test_vde_mode() {
+    use super::rug_sloth::P_512;
+
+    for mode in VdeMode::ALL {
+        assert_eq!(mode.to_string().parse::<VdeMode>().unwrap(), mode);
+    }
+    assert!("unknown".parse::<VdeMode>().is_err());
+
+    let p = Integer::from_str(P_512).unwrap();
+    let x = Integer::from(123456789u64);
+    let vde = VdeMode::Sloth.vde();
+    assert_eq!(vde.decode(&vde.encode(&x, &p, 3), &p, 3), x);
+    assert!(vde.slow_cost(&p, 3) >= 3 * 511);
+}
@@ -1,15 +1,9 @@
 use rug::{Integer, integer::Order};
-use super::rug_sloth::{sloth, sloth_inv};
+use super::mode::VdeMode;
 
-pub fn vde(x: &Vec<u8>, p: &Integer, t: usize, mode: &String, l: usize) -> Vec<u8> {
+pub fn vde(x: &Vec<u8>, p: &Integer, t: usize, mode: VdeMode, l: usize) -> Vec<u8> {
     let cur_x = Integer::from_digits(&x, Order::Lsf);
-    let y;
-    if mode == "sloth" {
-        y = sloth(&cur_x, p, t);
-    }
-    else {
-        y = sloth(&cur_x, p, t);
-    }
+    let y = mode.vde().encode(&cur_x, p, t);
     let mut y_bytes = y.to_digits::<u8>(Order::Lsf);
 
     if y_bytes.len() < l {
@@ -18,16 +12,9 @@ pub fn vde(x: &Vec<u8>, p: &Integer, t: usize, mode: &String, l: usize) -> Vec<u
     y_bytes
 }
 
-pub fn vde_inv(y: &Vec<u8>, p: &Integer, t: usize, mode: &String, l: usize) -> Vec<u8> {
+pub fn vde_inv(y: &Vec<u8>, p: &Integer, t: usize, mode: VdeMode, l: usize) -> Vec<u8> {
     let cur_y = Integer::from_digits(&y, Order::Lsf);
-    let x;
-
-    if mode == "sloth" {
-        x = sloth_inv(&cur_y, p, t);
-    }
-    else {
-        x = sloth_inv(&cur_y, p, t);
-    }
+    let x = mode.vde().decode(&cur_y, p, t);
     let mut x_bytes = x.to_digits::<u8>(Order::Lsf);
 
     if x_bytes.len() < l {
@@ -51,12 +38,12 @@ fn test_vde() {
         println!("sample: {:?}", i);
         
         let start = Instant::now();
-        let y = vde(&x, p, T, &"sloth".to_string(), 1024/8);
+        let y = vde(&x, p, T, VdeMode::Sloth, 1024/8);
         let cost1 = start.elapsed();
         println!("Vde: {:?}", cost1);
 
         let start = Instant::now();
-        let z = vde_inv(&y, p, T, &"sloth".to_string(), 1024/8);
+        let z = vde_inv(&y, p, T, VdeMode::Sloth, 1024/8);
         let cost2 = start.elapsed();
         println!("Vde inv: {:?}", cost2);
 